@@ -0,0 +1,263 @@
+use crate::{common::*, project::AssetOptions};
+use std::{
+    collections::BTreeSet,
+    fs::{copy, create_dir_all, read_link, read_to_string, remove_dir, remove_dir_all, remove_file, File},
+    io::prelude::*,
+    path::{Component, Path, PathBuf},
+};
+
+fn is_up_to_date(source: &Path, destination: &Path) -> bool {
+    let (source, destination) = match (source.metadata(), destination.symlink_metadata()) {
+        (Ok(source), Ok(destination)) => (source, destination),
+        _ => return false,
+    };
+
+    if !destination.is_file() || source.len() != destination.len() {
+        return false;
+    }
+
+    matches!((source.modified(), destination.modified()), (Ok(s), Ok(d)) if d >= s)
+}
+
+// Removes a file, directory or symlink without following symlinks, so that a
+// link back to the sources never has its target deleted.
+fn remove_path(path: &Path) -> bool {
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => remove_dir_all(path).is_ok(),
+        Ok(_) => remove_file(path).is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn is_inside_symlink(path: &Path, root: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|ancestor| *ancestor != root)
+        .any(|ancestor| matches!(ancestor.symlink_metadata(), Ok(m) if m.file_type().is_symlink()))
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, destination: &Path) -> std::io::Result<()> { std::os::unix::fs::symlink(source, destination) }
+
+#[cfg(windows)]
+fn symlink(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        std::os::windows::fs::symlink_dir(source, destination)
+    } else {
+        std::os::windows::fs::symlink_file(source, destination)
+    }
+}
+
+// Mirrors the configured asset paths, which are relative to `root`, into
+// `output_dir`, keeping their relative paths. `manifest_path` records what was
+// put there last time so that anything no longer listed can be removed.
+// Returns the number of files copied or linked and the number removed.
+pub fn mirror_assets(
+    options: &AssetOptions,
+    root: &Path,
+    output_dir: &Path,
+    manifest_path: &Path,
+) -> Result<(usize, usize), StrRet> {
+    let previous: BTreeSet<PathBuf> = read_to_string(manifest_path)
+        .unwrap_or_default()
+        .lines()
+        .map(PathBuf::from)
+        .collect();
+
+    let mut current = BTreeSet::new();
+
+    for path in &options.paths {
+        let source = Path::new(path);
+
+        if source
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!("Asset path \"{}\" must be relative to the project root", path).into());
+        }
+
+        if !root.join(source).exists() {
+            return Err(format!("Asset path \"{}\" does not exist", path).into());
+        }
+
+        if options.symlink {
+            current.insert(source.to_path_buf());
+        } else {
+            current.extend(
+                get_files_recursive(&root.join(source))
+                    .into_iter()
+                    .map(|file| file.strip_prefix(root).map(Path::to_path_buf).unwrap_or(file)),
+            );
+        }
+    }
+
+    // Stale entries go first: when switching between copies and symlinks the
+    // old layout has to be gone before the new one is created.
+    let mut removed = 0;
+
+    for stale in previous.difference(&current) {
+        let destination = output_dir.join(stale);
+
+        if is_inside_symlink(&destination, output_dir) || !remove_path(&destination) {
+            continue;
+        }
+
+        removed += 1;
+
+        for parent in destination.ancestors().skip(1) {
+            if parent == output_dir || remove_dir(parent).is_err() {
+                break;
+            }
+        }
+    }
+
+    let mut updated = 0;
+
+    for path in &current {
+        let destination = output_dir.join(path);
+
+        if let Some(parent) = destination.parent() {
+            if let Err(e) = create_dir_all(parent) {
+                return Err(format!("Could not create directory \"{}\": {}", parent.display(), e).into());
+            }
+        }
+
+        let source = root.join(path);
+
+        if options.symlink {
+            let target = get_absolute_path(&source.to_string_lossy());
+
+            if read_link(&destination).ok() == Some(target.clone()) {
+                continue;
+            }
+
+            remove_path(&destination);
+
+            if let Err(e) = symlink(&target, &destination) {
+                return Err(format!("Could not link asset \"{}\": {}", path.display(), e).into());
+            }
+        } else {
+            if is_up_to_date(&source, &destination) {
+                continue;
+            }
+
+            remove_path(&destination);
+
+            if let Err(e) = copy(&source, &destination) {
+                return Err(format!("Could not copy asset \"{}\": {}", path.display(), e).into());
+            }
+        }
+
+        updated += 1;
+    }
+
+    let manifest: Vec<String> = current.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+
+    match File::create(manifest_path).and_then(|mut file| file.write_all(manifest.join("\n").as_bytes())) {
+        Ok(_) => Ok((updated, removed)),
+        Err(e) => Err(format!("Could not write asset manifest: {}", e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        fs::{read_to_string, write},
+        process,
+        time::{Duration, SystemTime},
+    };
+
+    fn set_modified(path: &Path, age: u64) {
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+    }
+
+    #[test]
+    fn assets_are_copied_updated_and_removed() {
+        let directory = env::temp_dir().join(format!("ocean-assets-test-{}", process::id()));
+        let root = directory.join("project");
+        let output_dir = directory.join("build");
+        let manifest_path = directory.join("assets.txt");
+
+        create_dir_all(root.join("assets/images")).unwrap();
+        create_dir_all(&output_dir).unwrap();
+        write(root.join("assets/config.ini"), "debug = true").unwrap();
+        write(root.join("assets/images/logo.svg"), "<svg/>").unwrap();
+        write(root.join("LICENSE"), "MIT").unwrap();
+
+        let mut options = AssetOptions {
+            paths: vec!["assets".to_string(), "./LICENSE".to_string()],
+            ..AssetOptions::default()
+        };
+        let mirror = |options: &AssetOptions| mirror_assets(options, &root, &output_dir, &manifest_path).unwrap();
+
+        assert_eq!(mirror(&options), (3, 0));
+        assert_eq!(read_to_string(output_dir.join("assets/images/logo.svg")).unwrap(), "<svg/>");
+        assert_eq!(read_to_string(output_dir.join("LICENSE")).unwrap(), "MIT");
+        assert_eq!(
+            read_to_string(&manifest_path).unwrap(),
+            "LICENSE\nassets/config.ini\nassets/images/logo.svg"
+        );
+
+        // Files that are as new as their source are left alone.
+        assert_eq!(mirror(&options), (0, 0));
+
+        write(root.join("assets/config.ini"), "debug = false").unwrap();
+        set_modified(&output_dir.join("assets/config.ini"), 60);
+        assert_eq!(mirror(&options), (1, 0));
+        assert_eq!(read_to_string(output_dir.join("assets/config.ini")).unwrap(), "debug = false");
+
+        // Assets that are no longer listed are removed, along with the
+        // directories that they leave empty, but nothing else is.
+        write(output_dir.join("program"), "binary").unwrap();
+        remove_file(root.join("assets/images/logo.svg")).unwrap();
+        assert_eq!(mirror(&options), (0, 1));
+        assert!(!output_dir.join("assets/images").exists());
+
+        options.paths = vec!["./LICENSE".to_string()];
+        assert_eq!(mirror(&options), (0, 1));
+        assert!(!output_dir.join("assets").exists());
+        assert!(output_dir.join("LICENSE").is_file());
+        assert!(output_dir.join("program").is_file());
+
+        options.paths = vec!["../outside".to_string()];
+        assert!(mirror_assets(&options, &root, &output_dir, &manifest_path).is_err());
+        options.paths = vec!["missing".to_string()];
+        assert!(mirror_assets(&options, &root, &output_dir, &manifest_path).is_err());
+
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn assets_can_be_symlinked() {
+        let directory = env::temp_dir().join(format!("ocean-assets-symlink-test-{}", process::id()));
+        let root = directory.join("project");
+        let output_dir = directory.join("build");
+        let manifest_path = directory.join("assets.txt");
+
+        create_dir_all(root.join("assets")).unwrap();
+        create_dir_all(&output_dir).unwrap();
+        write(root.join("assets/config.ini"), "debug = true").unwrap();
+
+        let mut options = AssetOptions {
+            paths: vec!["assets".to_string()],
+            symlink: true,
+            ..AssetOptions::default()
+        };
+
+        assert_eq!(mirror_assets(&options, &root, &output_dir, &manifest_path).unwrap(), (1, 0));
+        assert_eq!(read_link(output_dir.join("assets")).unwrap(), root.join("assets"));
+        assert_eq!(mirror_assets(&options, &root, &output_dir, &manifest_path).unwrap(), (0, 0));
+
+        // Switching to copies replaces the link without touching its target.
+        options.symlink = false;
+        assert_eq!(mirror_assets(&options, &root, &output_dir, &manifest_path).unwrap(), (1, 1));
+        assert!(!output_dir.join("assets").symlink_metadata().unwrap().file_type().is_symlink());
+        assert!(root.join("assets/config.ini").is_file());
+
+        remove_dir_all(directory).unwrap();
+    }
+}
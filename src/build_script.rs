@@ -0,0 +1,223 @@
+use crate::{
+    cache::{Cache, FileData},
+    common::*,
+    project::Project,
+};
+use serde_derive::*;
+use std::{
+    fs::{create_dir_all, read, read_to_string, File},
+    io::prelude::*,
+    path::Path,
+};
+
+pub const BUILD_SCRIPT_PATH: &str = "build.c";
+
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct BuildScriptOutput {
+    pub script_hash: String,
+    pub defines: Vec<String>,
+    pub include_directories: Vec<String>,
+    pub libraries: Vec<String>,
+    pub library_directories: Vec<String>,
+    pub rerun_if_changed: Vec<FileData>,
+}
+
+impl BuildScriptOutput {
+    pub fn get_directory(project: &Project, build_mode: &str) -> String {
+        format!(
+            "{}/{}/build-script",
+            project.get_directories().get_objects_dir(),
+            build_mode
+        )
+    }
+
+    fn get_output_path(project: &Project, build_mode: &str) -> String {
+        format!("{}/output.toml", Self::get_directory(project, build_mode))
+    }
+
+    // Hashes the contents of a file, or of every file below a directory, so
+    // that rerun-if-changed can point at either.
+    pub fn hash_path(path: &Path) -> String {
+        let mut contents = vec![];
+
+        for file in get_files_recursive(path) {
+            contents.extend_from_slice(file.to_string_lossy().as_bytes());
+            contents.extend(read(&file).unwrap_or_default());
+        }
+
+        Cache::hash_bytes(&contents)
+    }
+
+    // Reads the directives that a build script printed. Returns the output and
+    // any warnings the script asked to be shown.
+    pub fn parse(stdout: &str, script_hash: String) -> Result<(Self, Vec<String>), StrRet> {
+        let mut output = Self {
+            script_hash,
+            ..Self::default()
+        };
+        let mut warnings = vec![];
+
+        for line in stdout.lines() {
+            let directive = match line.trim().strip_prefix("ocean:") {
+                Some(directive) => directive,
+                None => continue,
+            };
+
+            let (key, value) = match directive.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim().to_string()),
+                None => return Err(format!("Invalid build script directive \"{}\"", line).into()),
+            };
+
+            match key {
+                "define" => output.defines.push(value),
+                "include" => output.include_directories.push(value),
+                "link-lib" => output.libraries.push(value),
+                "link-search" => output.library_directories.push(value),
+                "rerun-if-changed" => {
+                    let hash = Self::hash_path(Path::new(&value));
+                    output.rerun_if_changed.push(FileData {
+                        path: value.into(),
+                        hash,
+                        ..FileData::default()
+                    });
+                },
+                "warning" => warnings.push(value),
+                _ => return Err(format!("Unknown build script directive \"{}\"", key).into()),
+            }
+        }
+
+        Ok((output, warnings))
+    }
+
+    pub fn load(project: &Project, build_mode: &str) -> Option<Self> {
+        toml::from_str(&read_to_string(Self::get_output_path(project, build_mode)).ok()?).ok()
+    }
+
+    pub fn save(&self, project: &Project, build_mode: &str) -> Result<(), StrRet> {
+        create_dir_all(Self::get_directory(project, build_mode)).unwrap_or(());
+
+        let contents = match toml::to_string_pretty(self) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("Could not serialise build script output: {}", e).into()),
+        };
+
+        match File::create(Self::get_output_path(project, build_mode))
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write build script output: {}", e).into()),
+        }
+    }
+
+    // A script that has not named any files to watch is rerun on every build,
+    // like a Cargo build script.
+    pub fn is_outdated(&self, script_hash: &str) -> bool {
+        self.script_hash != script_hash
+            || self.rerun_if_changed.is_empty()
+            || self
+                .rerun_if_changed
+                .iter()
+                .any(|file| Self::hash_path(&file.path) != file.hash)
+    }
+
+    pub fn get_compile_flags(&self) -> Vec<String> {
+        let defines = self.defines.iter().map(|define| format!("-D{}", define));
+        let includes = self.include_directories.iter().map(|dir| format!("-I{}", dir));
+
+        defines.chain(includes).collect()
+    }
+
+    pub fn get_link_flags(&self) -> Vec<String> {
+        let dirs = self.library_directories.iter().map(|dir| format!("-L{}", dir));
+        let libs = self.libraries.iter().map(|lib| format!("-l{}", lib));
+
+        dirs.chain(libs).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs::remove_dir_all, fs::write, process};
+
+    #[test]
+    fn directives_are_parsed() {
+        let stdout = "compiling things...
+ocean:define=VERSION=\"1.0\"
+ocean:define=DEBUG
+  ocean:include = generated/include
+ocean:link-lib=z
+ocean:link-search=/opt/zlib/lib
+ocean:warning=zlib was not found with pkg-config
+OCEAN:define=IGNORED
+";
+        let (output, warnings) = BuildScriptOutput::parse(stdout, "hash".to_string()).unwrap();
+
+        assert_eq!(output.script_hash, "hash");
+        assert_eq!(output.defines, ["VERSION=\"1.0\"", "DEBUG"]);
+        assert_eq!(output.include_directories, ["generated/include"]);
+        assert_eq!(output.libraries, ["z"]);
+        assert_eq!(output.library_directories, ["/opt/zlib/lib"]);
+        assert!(output.rerun_if_changed.is_empty());
+        assert_eq!(warnings, ["zlib was not found with pkg-config"]);
+
+        assert_eq!(
+            output.get_compile_flags(),
+            ["-DVERSION=\"1.0\"", "-DDEBUG", "-Igenerated/include"]
+        );
+        assert_eq!(output.get_link_flags(), ["-L/opt/zlib/lib", "-lz"]);
+    }
+
+    #[test]
+    fn rerun_if_changed_hashes_the_files_it_names() {
+        let directory = env::temp_dir().join(format!("ocean-build-script-test-{}", process::id()));
+        create_dir_all(&directory).unwrap();
+
+        let file = directory.join("schema.txt");
+        write(&file, "version 1").unwrap();
+
+        let stdout = format!("ocean:rerun-if-changed={}\n", directory.display());
+        let (output, _) = BuildScriptOutput::parse(&stdout, "hash".to_string()).unwrap();
+
+        assert_eq!(output.rerun_if_changed.len(), 1);
+        assert_eq!(output.rerun_if_changed[0].path, directory);
+        assert_eq!(output.rerun_if_changed[0].hash, BuildScriptOutput::hash_path(&directory));
+        assert!(!output.is_outdated("hash"));
+        assert!(output.is_outdated("other hash"));
+
+        write(&file, "version 2").unwrap();
+        assert!(output.is_outdated("hash"));
+
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn scripts_without_rerun_if_changed_always_rerun() {
+        let (output, _) = BuildScriptOutput::parse("ocean:define=A\n", "hash".to_string()).unwrap();
+
+        assert!(output.is_outdated("hash"));
+    }
+
+    #[test]
+    fn unknown_and_malformed_directives_are_errors() {
+        for stdout in [
+            "ocean:link-libs=z",
+            "ocean:=z",
+            "ocean:define",
+            "ocean:",
+            "ocean:warning: missing equals sign",
+        ] {
+            assert!(
+                BuildScriptOutput::parse(stdout, String::new()).is_err(),
+                "\"{}\" should not parse",
+                stdout
+            );
+        }
+
+        // Lines that are not directives are the script's own output.
+        let stdout = "ocean\n: define=A\nocean define=A\n";
+        let (output, warnings) = BuildScriptOutput::parse(stdout, String::new()).unwrap();
+        assert!(output.defines.is_empty());
+        assert!(warnings.is_empty());
+    }
+}
@@ -1,25 +1,148 @@
-use crate::{common::StrRet, project::Project};
+use crate::{common::StrRet, compiler::CompilerIdentity, project::Project};
 use serde_derive::*;
+use sha2::{Digest, Sha256};
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::BTreeMap,
+    env,
     fs::*,
-    hash::*,
     io::prelude::*,
+    mem,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+// Environment variables that change what the compiler or linker produces
+// without showing up in their command line.
+const FINGERPRINT_ENV: &[&str] = &[
+    "CPATH",
+    "C_INCLUDE_PATH",
+    "CPLUS_INCLUDE_PATH",
+    "LIBRARY_PATH",
+    "GCC_EXEC_PREFIX",
+    "COMPILER_PATH",
+    "SDKROOT",
+    "MACOSX_DEPLOYMENT_TARGET",
+];
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct FileData {
     pub path: PathBuf,
     pub hash: String,
+    // The size and modification time (in nanoseconds since the Unix epoch) that
+    // the file had when it was hashed. If both are the same on the next build,
+    // the file is not read again.
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified: u64,
 }
 
-#[derive(Deserialize, Serialize)]
+impl FileData {
+    fn get_modified(metadata: &Metadata) -> u64 {
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+
+        // A file can be changed again within the resolution of its timestamp,
+        // so recent timestamps are not trusted and the file is hashed again.
+        if SystemTime::now().duration_since(modified).unwrap_or_default() < Duration::from_secs(2) {
+            return 0;
+        }
+
+        modified
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or(0)
+    }
+
+    // Hashes the file at `path`, unless `previous` shows that it has not been
+    // modified since it was last hashed.
+    fn read(path: PathBuf, previous: Option<&FileData>) -> Result<Self, StrRet> {
+        let metadata = match metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => return Err(format!("Could not read \"{}\": {}", path.display(), e).into()),
+        };
+        let size = metadata.len();
+        let modified = Self::get_modified(&metadata);
+
+        if let Some(previous) = previous {
+            if modified != 0 && previous.size == size && previous.modified == modified {
+                return Ok(Self { path, ..previous.clone() });
+            }
+        }
+
+        let contents = match read(&path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("Could not read \"{}\": {}", path.display(), e).into()),
+        };
+
+        Ok(Self {
+            hash: Cache::hash_bytes(&contents),
+            path,
+            size,
+            modified,
+        })
+    }
+}
+
+// The files that differ between the cache and the project. A renamed file is
+// both removed and added.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool { self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty() }
+
+    // Whether `path` has to be compiled again because of its contents.
+    pub fn needs_compiling(&self, path: &Path) -> bool {
+        self.added.iter().chain(&self.modified).any(|changed| changed == path)
+    }
+}
+
+// Bumped whenever the format of the build cache changes, so that a cache
+// written by another version of Ocean is thrown away rather than misread.
+const CACHE_VERSION: u32 = 1;
+
+// The state of the last build in a profile, kept in
+// <build_dir>/.ocean/<profile>/cache.toml.
+#[derive(Default, Deserialize, Serialize)]
 pub struct Cache {
+    #[serde(default)]
+    version: u32,
+    // The features that the cached objects were built with. It has to come
+    // before the array of tables in the file.
+    #[serde(default)]
+    features: Vec<String>,
     files: Vec<FileData>,
+    // The headers and sources that the objects were compiled from, as listed in
+    // their dependency files, sorted by path. An empty list would be written as
+    // a value after the tables, which TOML does not allow.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<FileData>,
+    // The dependencies read during this build, or None for the ones that could
+    // not be read.
+    #[serde(skip)]
+    read_dependencies: BTreeMap<PathBuf, Option<FileData>>,
+    // The fingerprints of the commands that each object and output was last
+    // built with, keyed by their path.
+    #[serde(default)]
+    objects: BTreeMap<String, String>,
+    #[serde(default)]
+    outputs: BTreeMap<String, String>,
 }
 
 impl Cache {
+    // SHA-256, so that hashes stay the same across Rust and Ocean versions.
+    pub fn hash_bytes(bytes: &[u8]) -> String {
+        Sha256::digest(bytes)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
     fn get_dir_contents(path: PathBuf) -> Option<Vec<PathBuf>> {
         let path = Path::new(&path);
 
@@ -43,7 +166,7 @@ impl Cache {
         }
     }
 
-    fn get_all_files(project: &Project) -> Result<Vec<FileData>, StrRet> {
+    fn get_all_files(project: &Project, previous: &[FileData]) -> Result<Vec<FileData>, StrRet> {
         let dir_contents: Vec<PathBuf> = match read_dir(project.get_directories().get_source_dir()) {
             Ok(cont) => cont,
             Err(e) =>
@@ -54,7 +177,6 @@ impl Cache {
                 )
                 .into()),
         }
-        .into_iter()
         .map(|x| x.unwrap().path())
         .collect();
 
@@ -66,62 +188,606 @@ impl Cache {
             })
         });
 
-        let mut files = vec![];
-        for item in paths {
-            let file_data = File::open(item.clone());
-            let mut buffer = Vec::<u8>::new();
-
-            file_data
-                .unwrap()
-                .read_to_end(&mut buffer)
-                .expect("Could not read file.");
-
-            files.push(FileData {
-                path: item.clone(),
-                hash: {
-                    let mut s = DefaultHasher::new();
-                    s.write(&buffer[..]);
-                    format!("{:x}", s.finish())
-                },
-            });
+        for source in project.get_feature_sources() {
+            let source = PathBuf::from(source);
+
+            if source.is_file() && !paths.iter().any(|path| path.ends_with(&source)) {
+                paths.push(source);
+            }
+        }
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let previous = previous.iter().find(|file| file.path == path);
+                FileData::read(path, previous)
+            })
+            .collect()
+    }
+
+    pub fn get_path(project: &Project, build_mode: &str) -> String {
+        format!("{}/.ocean/{}/cache.toml", project.get_directories().get_build_dir(), build_mode)
+    }
+
+    // Returns the cache of the last build in `build_mode`, if there is one that
+    // this version of Ocean can read.
+    pub fn load(project: &Project, build_mode: &str) -> Option<Self> {
+        let cache: Self = toml::from_str(&read_to_string(Self::get_path(project, build_mode)).ok()?).ok()?;
+
+        if cache.version != CACHE_VERSION {
+            return None;
+        }
+
+        Some(cache)
+    }
+
+    pub fn save(&self, project: &Project, build_mode: &str) -> Result<(), StrRet> {
+        let path = Self::get_path(project, build_mode);
+
+        if let Some(parent) = Path::new(&path).parent() {
+            create_dir_all(parent).unwrap_or(());
+        }
+
+        let contents = match toml::to_string_pretty(self) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("Could not serialise {}: {}", path, e).into()),
+        };
+
+        match File::create(&path).and_then(|mut file| file.write_all(contents.as_bytes())) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write {}: {}", path, e).into()),
         }
+    }
+
+    // Older versions of Ocean kept the build cache in Ocean.lock, which is now
+    // meant for resolved dependencies. A lock file that holds a build cache is
+    // removed, so that it is not mistaken for one that should be committed.
+    pub fn remove_legacy_lock_file() {
+        let is_build_cache = read_to_string("Ocean.lock")
+            .ok()
+            .and_then(|contents| toml::from_str::<toml::Value>(&contents).ok())
+            .is_some_and(|lock| lock.get("files").is_some());
 
-        Ok(files)
+        if is_build_cache && remove_file("Ocean.lock").is_ok() {
+            println!("Removed the build cache from Ocean.lock, it is now kept in the build directory.");
+        }
     }
 
     pub fn new(project: &Project) -> Result<Self, StrRet> {
         Ok(Self {
-            files: Self::get_all_files(project)?,
+            version: CACHE_VERSION,
+            features: project.get_enabled_features().into_iter().collect(),
+            files: Self::get_all_files(project, &[])?,
+            ..Self::default()
         })
     }
 
-    pub fn get_changed(&self, project: &Project) -> Result<Vec<PathBuf>, StrRet> {
-        if !Path::new("Ocean.lock").exists() {
-            return Err("Cannot find Ocean.lock in project root.".into());
+    // Hashes everything that goes into running `command` apart from its input
+    // files: the command line, the compiler's family and version, and the
+    // environment variables that the toolchain reads.
+    pub fn get_fingerprint(command: &Command, identity: &CompilerIdentity) -> String {
+        let mut parts = vec![
+            command.get_program().to_string_lossy().into_owned(),
+            identity.family.clone(),
+            identity.version.clone(),
+        ];
+
+        parts.extend(command.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+        parts.extend(
+            FINGERPRINT_ENV
+                .iter()
+                .map(|name| format!("{}={}", name, env::var(name).unwrap_or_default())),
+        );
+
+        Self::hash_bytes(parts.join("\0").as_bytes())
+    }
+
+    // Adds the files that a compile read, as listed in the dependency file that
+    // the compiler wrote with -MMD, to the fingerprint of its command. Returns
+    // None if any of them cannot be read, so that the file is recompiled. Like
+    // the project's files, dependencies are only hashed again when their size
+    // or modification time changed, and only once per build.
+    pub fn add_dependencies(&mut self, fingerprint: &str, dependency_file: &Path) -> Option<String> {
+        let rules = read_to_string(dependency_file).ok()?;
+        let mut parts = vec![fingerprint.to_string()];
+
+        for dependency in parse_dependencies(&rules) {
+            if !self.read_dependencies.contains_key(&dependency) {
+                let previous = self
+                    .dependencies
+                    .binary_search_by(|file| file.path.cmp(&dependency))
+                    .ok()
+                    .map(|index| &self.dependencies[index]);
+                let file = FileData::read(dependency.clone(), previous).ok();
+
+                self.read_dependencies.insert(dependency.clone(), file);
+            }
+
+            let file = self.read_dependencies[&dependency].as_ref()?;
+
+            parts.push(dependency.to_string_lossy().into_owned());
+            parts.push(file.hash.clone());
         }
 
-        let mut changed = vec![];
-        let current_files = Self::get_all_files(project)?;
-        let diffs = self.files.iter().zip(current_files.iter());
+        Some(Self::hash_bytes(parts.join("\0").as_bytes()))
+    }
+
+    // Keeps the dependencies read during this build for the next one. Every
+    // object is looked at in a build that saves the cache, so the ones that
+    // were not read are no longer included by anything.
+    fn keep_read_dependencies(&mut self) {
+        self.dependencies = mem::take(&mut self.read_dependencies).into_values().flatten().collect();
+    }
+
+    // Compares two lists of files by path, so that adding or removing a file
+    // does not affect how the others are compared.
+    pub fn diff(old_files: &[FileData], new_files: &[FileData]) -> Changes {
+        let old_hashes: BTreeMap<&PathBuf, &String> = old_files.iter().map(|file| (&file.path, &file.hash)).collect();
+        let new_hashes: BTreeMap<&PathBuf, &String> = new_files.iter().map(|file| (&file.path, &file.hash)).collect();
+
+        let mut changes = Changes::default();
 
-        for (old_diff, curr_diff) in diffs {
-            if old_diff.hash != curr_diff.hash {
-                changed.push(curr_diff.path.clone());
+        for (path, hash) in &new_hashes {
+            match old_hashes.get(path) {
+                None => changes.added.push((*path).clone()),
+                Some(old_hash) if old_hash != hash => changes.modified.push((*path).clone()),
+                _ => (),
             }
         }
 
-        Ok(changed)
+        for path in old_hashes.keys() {
+            if !new_hashes.contains_key(path) {
+                changes.removed.push((*path).clone());
+            }
+        }
+
+        changes
     }
 
-    pub fn update_cache(&mut self, project: &Project) -> Result<(), StrRet> {
-        if !Path::new("Ocean.lock").exists() {
-            return Err("Cannot find Ocean.lock in project root.".into());
+    // The fingerprint of a link also covers the contents of the objects that
+    // are linked, so recompiling a file to the same object does not relink.
+    pub fn get_link_fingerprint(command: &Command, identity: &CompilerIdentity, objects: &[String]) -> String {
+        let mut contents = Self::get_fingerprint(command, identity);
+
+        for object in objects {
+            contents.push_str(&Self::hash_bytes(&read(object).unwrap_or_default()));
         }
 
-        self.files = Self::get_all_files(project).unwrap();
+        Self::hash_bytes(contents.as_bytes())
+    }
+
+    pub fn get_changed(&self, project: &Project) -> Result<Changes, StrRet> {
+        Ok(Self::diff(&self.files, &Self::get_all_files(project, &self.files)?))
+    }
+
+    pub fn update_cache(&mut self, project: &Project) -> Result<(), StrRet> {
+        self.files = Self::get_all_files(project, &self.files)?;
+        self.features = project.get_enabled_features().into_iter().collect();
+        self.keep_read_dependencies();
 
         Ok(())
     }
 
-    pub fn get_files(&self) -> &Vec<FileData> { &self.files }
+    pub fn get_features(&self) -> &Vec<String> { &self.features }
+
+    pub fn get_object_fingerprint(&self, object: &str) -> Option<&String> { self.objects.get(object) }
+    pub fn set_object_fingerprint(&mut self, object: String, fingerprint: String) {
+        self.objects.insert(object, fingerprint);
+    }
+    pub fn remove_object_fingerprint(&mut self, object: &str) { self.objects.remove(object); }
+
+    pub fn get_output_fingerprint(&self, output: &str) -> Option<&String> { self.outputs.get(output) }
+    pub fn set_output_fingerprint(&mut self, output: String, fingerprint: String) {
+        self.outputs.insert(output, fingerprint);
+    }
+}
+
+// Returns the prerequisites of the Make rules that the compiler writes with -M
+// and its variants. Lines are continued with a backslash, and spaces in paths
+// are escaped with one.
+pub fn parse_dependencies(rules: &str) -> Vec<PathBuf> {
+    let rules = rules.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut dependencies = vec![];
+
+    for rule in rules.lines() {
+        let prerequisites = match rule.split_once(": ") {
+            Some((_, prerequisites)) => prerequisites,
+            None => continue,
+        };
+
+        let mut current = String::new();
+        let mut chars = prerequisites.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => {
+                    current.push(' ');
+                    chars.next();
+                },
+                c if c.is_whitespace() =>
+                    if !current.is_empty() {
+                        dependencies.push(PathBuf::from(mem::take(&mut current)));
+                    },
+                c => current.push(c),
+            }
+        }
+
+        if !current.is_empty() {
+            dependencies.push(PathBuf::from(current));
+        }
+    }
+
+    dependencies
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub hash: String,
+    pub success: bool,
+    pub output: String,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ResultCache {
+    files: Vec<FileResult>,
+}
+
+impl ResultCache {
+    pub fn load(path: &str) -> Self {
+        match read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), StrRet> {
+        if let Some(parent) = Path::new(path).parent() {
+            create_dir_all(parent).unwrap_or(());
+        }
+
+        let contents = match toml::to_string_pretty(self) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("Could not serialise {}: {}", path, e).into()),
+        };
+
+        match File::create(path).and_then(|mut file| file.write_all(contents.as_bytes())) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write {}: {}", path, e).into()),
+        }
+    }
+
+    // The key covers the file, every header that it includes and the command it
+    // is checked with, so editing a header or changing flags invalidates earlier
+    // results. The headers are listed by running `compiler`, the compiler and
+    // flags that the file is built with, with -M. Returns None if they could not
+    // be listed, in which case the file is always checked again.
+    pub fn get_key(path: &Path, compiler: &[String], command: &[String]) -> Option<String> {
+        let output = Command::new(&compiler[0])
+            .args(&compiler[1..])
+            .arg("-M")
+            .arg(path)
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let mut contents = vec![];
+
+        for dependency in parse_dependencies(&String::from_utf8_lossy(&output.stdout)) {
+            contents.extend_from_slice(dependency.to_string_lossy().as_bytes());
+            contents.push(0);
+            File::open(&dependency).ok()?.read_to_end(&mut contents).ok()?;
+        }
+
+        contents.extend_from_slice(command.join("\0").as_bytes());
+
+        Some(Cache::hash_bytes(&contents))
+    }
+
+    pub fn get(&self, path: &Path, hash: &str) -> Option<&FileResult> {
+        self.files.iter().find(|f| f.path == path && !f.hash.is_empty() && f.hash == hash)
+    }
+
+    pub fn insert(&mut self, result: FileResult) {
+        self.files.retain(|f| f.path != result.path);
+        self.files.push(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, hash: &str) -> FileData {
+        FileData {
+            path: PathBuf::from(path),
+            hash: hash.to_string(),
+            ..FileData::default()
+        }
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> { paths.iter().map(PathBuf::from).collect() }
+
+    #[test]
+    fn unchanged_files_have_no_changes() {
+        let files = vec![file("src/a.c", "1"), file("src/b.c", "2")];
+
+        assert!(Cache::diff(&files, &files).is_empty());
+    }
+
+    #[test]
+    fn order_does_not_matter() {
+        let old_files = vec![file("src/a.c", "1"), file("src/b.c", "2")];
+        let new_files = vec![file("src/b.c", "2"), file("src/a.c", "1")];
+
+        assert!(Cache::diff(&old_files, &new_files).is_empty());
+    }
+
+    #[test]
+    fn modified_file_is_reported() {
+        let old_files = vec![file("src/a.c", "1"), file("src/b.c", "2")];
+        let new_files = vec![file("src/a.c", "1"), file("src/b.c", "3")];
+
+        assert_eq!(
+            Cache::diff(&old_files, &new_files),
+            Changes {
+                modified: paths(&["src/b.c"]),
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn added_file_does_not_shift_others() {
+        let old_files = vec![file("src/b.c", "2"), file("src/c.c", "3")];
+        let new_files = vec![file("src/a.c", "1"), file("src/b.c", "2"), file("src/c.c", "3")];
+
+        assert_eq!(
+            Cache::diff(&old_files, &new_files),
+            Changes {
+                added: paths(&["src/a.c"]),
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn removed_file_does_not_shift_others() {
+        let old_files = vec![file("src/a.c", "1"), file("src/b.c", "2"), file("src/c.c", "3")];
+        let new_files = vec![file("src/b.c", "2"), file("src/c.c", "3")];
+
+        assert_eq!(
+            Cache::diff(&old_files, &new_files),
+            Changes {
+                removed: paths(&["src/a.c"]),
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn first_file_can_be_removed_and_another_modified() {
+        let old_files = vec![file("src/a.c", "1"), file("src/b.c", "2")];
+        let new_files = vec![file("src/b.c", "4")];
+
+        assert_eq!(
+            Cache::diff(&old_files, &new_files),
+            Changes {
+                removed: paths(&["src/a.c"]),
+                modified: paths(&["src/b.c"]),
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn renamed_file_is_removed_and_added() {
+        let old_files = vec![file("src/a.c", "1"), file("src/old.c", "2")];
+        let new_files = vec![file("src/a.c", "1"), file("src/new.c", "2")];
+        let changes = Cache::diff(&old_files, &new_files);
+
+        assert_eq!(
+            changes,
+            Changes {
+                added: paths(&["src/new.c"]),
+                removed: paths(&["src/old.c"]),
+                ..Changes::default()
+            }
+        );
+        assert!(changes.needs_compiling(Path::new("src/new.c")));
+        assert!(!changes.needs_compiling(Path::new("src/old.c")));
+        assert!(!changes.needs_compiling(Path::new("src/a.c")));
+    }
+
+    #[test]
+    fn everything_is_added_to_an_empty_cache() {
+        let new_files = vec![file("src/a.c", "1"), file("src/b.c", "2")];
+
+        assert_eq!(
+            Cache::diff(&[], &new_files),
+            Changes {
+                added: paths(&["src/a.c", "src/b.c"]),
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn hashes_are_sha256() {
+        assert_eq!(
+            Cache::hash_bytes(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn unmodified_files_are_not_hashed_again() {
+        let path = env::temp_dir().join(format!("ocean-cache-test-{}.c", std::process::id()));
+        write(&path, "int main(void) { return 0; }").unwrap();
+
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+        drop(file);
+
+        let hashed = FileData::read(path.clone(), None).unwrap();
+        assert_eq!(hashed.hash, Cache::hash_bytes(b"int main(void) { return 0; }"));
+        assert_ne!(hashed.modified, 0);
+
+        // A cached hash is trusted while the size and modification time match.
+        let previous = FileData {
+            hash: "cached".to_string(),
+            ..hashed.clone()
+        };
+        assert_eq!(FileData::read(path.clone(), Some(&previous)).unwrap().hash, "cached");
+
+        let resized = FileData {
+            size: hashed.size + 1,
+            ..previous.clone()
+        };
+        assert_eq!(FileData::read(path.clone(), Some(&resized)).unwrap().hash, hashed.hash);
+
+        // Files that were just modified are always hashed.
+        write(&path, "int main(void) { return 1; }").unwrap();
+        let rewritten = FileData::read(path.clone(), Some(&previous)).unwrap();
+        assert_eq!(rewritten.modified, 0);
+        assert_eq!(rewritten.hash, Cache::hash_bytes(b"int main(void) { return 1; }"));
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fingerprint_depends_on_the_command_and_compiler() {
+        let identity = CompilerIdentity {
+            family: "gcc".to_string(),
+            version: "13.2.0".to_string(),
+        };
+        let mut command = Command::new("gcc");
+        command.args(["-O2", "-c", "src/a.c"]);
+        let fingerprint = Cache::get_fingerprint(&command, &identity);
+
+        let mut other_flags = Command::new("gcc");
+        other_flags.args(["-O3", "-c", "src/a.c"]);
+
+        let other_version = CompilerIdentity {
+            version: "14.1.0".to_string(),
+            ..identity.clone()
+        };
+
+        assert_eq!(fingerprint, Cache::get_fingerprint(&command, &identity));
+        assert_ne!(fingerprint, Cache::get_fingerprint(&other_flags, &identity));
+        assert_ne!(fingerprint, Cache::get_fingerprint(&command, &other_version));
+    }
+
+    #[test]
+    fn dependencies_are_parsed_from_make_rules() {
+        let rules = "obj/main.o: src/main.c /usr/include/stdio.h src/my\\ header.h \\\n /usr/include/features.h\n";
+
+        assert_eq!(
+            parse_dependencies(rules),
+            paths(&[
+                "src/main.c",
+                "/usr/include/stdio.h",
+                "src/my header.h",
+                "/usr/include/features.h"
+            ])
+        );
+        assert!(parse_dependencies("obj/main.o:\n").is_empty());
+    }
+
+    #[test]
+    fn dependency_fingerprints_change_with_included_headers() {
+        let directory = env::temp_dir().join(format!("ocean-dependency-test-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+
+        let header = directory.join("message.h");
+        let dependency_file = directory.join("main.d");
+        write(&header, "#define MESSAGE 0\n").unwrap();
+        write(&dependency_file, format!("main.o: {}\n", header.display())).unwrap();
+
+        let fingerprint = Cache::default().add_dependencies("command", &dependency_file).unwrap();
+        assert_ne!(fingerprint, Cache::hash_bytes(b"command"));
+        assert_eq!(
+            Cache::default().add_dependencies("command", &dependency_file),
+            Some(fingerprint.clone())
+        );
+
+        write(&header, "#define MESSAGE 1\n").unwrap();
+        assert_ne!(
+            Cache::default().add_dependencies("command", &dependency_file),
+            Some(fingerprint)
+        );
+
+        // Files that are missing, like the dependency file of an object that
+        // was never compiled, always cause a recompile.
+        remove_file(&header).unwrap();
+        assert_eq!(Cache::default().add_dependencies("command", &dependency_file), None);
+        remove_file(&dependency_file).unwrap();
+        assert_eq!(Cache::default().add_dependencies("command", &dependency_file), None);
+
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn unmodified_dependencies_are_not_hashed_again() {
+        let directory = env::temp_dir().join(format!("ocean-dependency-hash-test-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+
+        let header = directory.join("message.h");
+        let dependency_file = directory.join("main.d");
+        write(&header, "#define MESSAGE 0\n").unwrap();
+        write(&dependency_file, format!("main.o: {}\n", header.display())).unwrap();
+
+        let file = File::options().write(true).open(&header).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+        drop(file);
+
+        let mut cache = Cache::default();
+        let fingerprint = cache.add_dependencies("command", &dependency_file).unwrap();
+        cache.keep_read_dependencies();
+        assert_eq!(cache.dependencies.len(), 1);
+
+        // The next build trusts the stored hash while the size and modification
+        // time match, so a stale hash shows that the header was not read.
+        cache.dependencies[0].hash = "cached".to_string();
+        assert_ne!(cache.add_dependencies("command", &dependency_file), Some(fingerprint.clone()));
+
+        let mut rebuilt = Cache {
+            dependencies: vec![FileData {
+                size: 0,
+                ..cache.dependencies[0].clone()
+            }],
+            ..Cache::default()
+        };
+        assert_eq!(rebuilt.add_dependencies("command", &dependency_file), Some(fingerprint));
+
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn result_keys_change_with_included_headers() {
+        let directory = env::temp_dir().join(format!("ocean-result-key-test-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+
+        let source = directory.join("main.c");
+        let header = directory.join("message.h");
+        write(&source, "#include \"message.h\"\nint main(void) { return MESSAGE; }\n").unwrap();
+        write(&header, "#define MESSAGE 0\n").unwrap();
+
+        let compiler = vec!["cc".to_string()];
+        let command = vec!["cc".to_string(), "-fsyntax-only".to_string()];
+
+        // Skipped where there is no C compiler to list the headers with.
+        if let Some(key) = ResultCache::get_key(&source, &compiler, &command) {
+            assert_eq!(ResultCache::get_key(&source, &compiler, &command), Some(key.clone()));
+
+            write(&header, "#define MESSAGE 1\n").unwrap();
+            assert_ne!(ResultCache::get_key(&source, &compiler, &command), Some(key));
+        }
+
+        remove_dir_all(directory).unwrap();
+    }
 }
@@ -0,0 +1,453 @@
+use crate::{
+    cache::Cache,
+    common::*,
+    compiler::CompilerIdentity,
+    project::{ChecksOptions, Project},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{create_dir_all, read_to_string, remove_dir_all, File},
+    io::prelude::*,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+pub const CHECKS_HEADER: &str = "ocean_checks.h";
+
+// The results of the checks do not depend on the build mode, so they are shared
+// between all of them.
+pub fn get_directory(project: &Project) -> String {
+    format!("{}/checks", project.get_directories().get_objects_dir())
+}
+
+pub fn get_header_path(project: &Project) -> String { format!("{}/{}", get_directory(project), CHECKS_HEADER) }
+
+fn get_cache_path(project: &Project) -> String { format!("{}/results.toml", get_directory(project)) }
+
+// Types are assumed not to exist rather than be larger than this, so that a
+// compiler that never confirms a size cannot keep the search going forever.
+const MAX_TYPE_SIZE: u64 = 1 << 16;
+
+enum Check {
+    Header(String),
+    Function(String),
+    Symbol(String, String),
+    TypeSize(String),
+    Library(String),
+}
+
+fn to_macro_name(name: &str) -> String {
+    name.trim()
+        .replace('*', "P")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+impl Check {
+    fn get_name(&self) -> String {
+        match self {
+            Check::Header(header) => format!("header {}", header),
+            Check::Function(function) => format!("function {}", function),
+            Check::Symbol(symbol, header) => format!("symbol {} in {}", symbol, header),
+            Check::TypeSize(type_name) => format!("size of {}", type_name),
+            Check::Library(library) => format!("library {}", library),
+        }
+    }
+
+    // Returns the name that the result is cached under. Type sizes depend on
+    // the headers that were found, since those can define the type.
+    fn get_key(&self, headers: &[String]) -> String {
+        match self {
+            Check::TypeSize(_) => format!("{} with {}", self.get_name(), headers.join(", ")),
+            _ => self.get_name(),
+        }
+    }
+
+    fn get_macro(&self) -> String {
+        match self {
+            Check::Header(name) | Check::Function(name) | Check::Symbol(name, _) =>
+                format!("HAVE_{}", to_macro_name(name)),
+            Check::TypeSize(type_name) => format!("SIZEOF_{}", to_macro_name(type_name)),
+            Check::Library(library) => format!("HAVE_LIB{}", to_macro_name(library)),
+        }
+    }
+}
+
+// Compiles the small programs that the checks are made of, with the project's
+// compiler and flags. Warnings are turned off, since the programs are written
+// to provoke them.
+struct Prober {
+    compiler: String,
+    extension: String,
+    flags: Vec<String>,
+    link_flags: Vec<String>,
+    directory: PathBuf,
+}
+
+impl Prober {
+    fn compiles(&self, name: &str, source: &str, link: bool, extra_flags: &[String]) -> bool {
+        let source_path = self.directory.join(format!("{}.{}", name, self.extension));
+
+        if File::create(&source_path)
+            .and_then(|mut file| file.write_all(source.as_bytes()))
+            .is_err()
+        {
+            return false;
+        }
+
+        let mut command = Command::new(&self.compiler);
+        command.args(&self.flags).arg("-w").arg(&source_path);
+
+        if link {
+            command
+                .arg("-o")
+                .arg(self.directory.join(name))
+                .args(&self.link_flags)
+                .args(extra_flags);
+        } else {
+            command.arg("-fsyntax-only");
+        }
+
+        matches!(
+            command.stdout(Stdio::null()).stderr(Stdio::null()).status(),
+            Ok(status) if status.success()
+        )
+    }
+
+    // Finds the size of a type by asking the compiler whether it is at most N
+    // bytes, so that nothing has to be run and cross compilers work too.
+    // Returns 0 if the type does not exist or is larger than MAX_TYPE_SIZE.
+    fn get_type_size(&self, name: &str, includes: &str, type_name: &str) -> u64 {
+        let at_most = |size: u64| {
+            self.compiles(
+                name,
+                &format!(
+                    "{}int main(void) {{ static char probe[(sizeof({}) <= {}) ? 1 : -1]; (void) probe; return 0; }}\n",
+                    includes, type_name, size
+                ),
+                false,
+                &[],
+            )
+        };
+
+        let exists = self.compiles(
+            name,
+            &format!("{}int main(void) {{ return (int) sizeof({}); }}\n", includes, type_name),
+            false,
+            &[],
+        );
+
+        if !exists {
+            return 0;
+        }
+
+        let mut high = 1;
+        while !at_most(high) {
+            if high >= MAX_TYPE_SIZE {
+                return 0;
+            }
+
+            high *= 2;
+        }
+
+        let mut low = high / 2 + 1;
+        while low < high {
+            let middle = (low + high) / 2;
+
+            if at_most(middle) {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        high
+    }
+
+    // Returns 1 or 0 for whether the check passed, or the size of the type for
+    // type size checks.
+    fn run(&self, index: usize, check: &Check, includes: &str) -> u64 {
+        let name = format!("probe{}", index);
+
+        let passed = match check {
+            Check::Header(header) =>
+                self.compiles(&name, &format!("#include <{}>\nint main(void) {{ return 0; }}\n", header), false, &[]),
+            Check::Function(function) => self.compiles(
+                &name,
+                &format!(
+                    "#ifdef __cplusplus\nextern \"C\"\n#endif\nchar {0}(void);\nint main(void) {{ return (int) {0}(); \
+                     }}\n",
+                    function
+                ),
+                true,
+                &[],
+            ),
+            Check::Symbol(symbol, header) => self.compiles(
+                &name,
+                &format!(
+                    "#include <{}>\nint main(void) {{\n#ifndef {1}\n    (void) {1};\n#endif\n    return 0;\n}}\n",
+                    header, symbol
+                ),
+                false,
+                &[],
+            ),
+            Check::TypeSize(type_name) => return self.get_type_size(&name, includes, type_name),
+            Check::Library(library) => self.compiles(
+                &name,
+                "int main(void) { return 0; }\n",
+                true,
+                &[format!("-l{}", library)],
+            ),
+        };
+
+        passed as u64
+    }
+}
+
+fn get_checks(options: &ChecksOptions) -> Vec<Check> {
+    let mut checks = vec![];
+
+    checks.extend(options.headers.iter().cloned().map(Check::Header));
+    checks.extend(options.functions.iter().cloned().map(Check::Function));
+    checks.extend(
+        options
+            .symbols
+            .iter()
+            .map(|(symbol, header)| Check::Symbol(symbol.clone(), header.clone())),
+    );
+    checks.extend(options.type_sizes.iter().cloned().map(Check::TypeSize));
+    checks.extend(options.libraries.iter().cloned().map(Check::Library));
+
+    checks
+}
+
+// Returns the headers that type sizes are checked with: the ones that define
+// the standard types, and every header in the [checks] section that was found.
+fn get_found_headers(options: &ChecksOptions, results: &BTreeMap<String, u64>) -> Vec<String> {
+    ["stddef.h", "stdint.h"]
+        .iter()
+        .map(|header| header.to_string())
+        .chain(
+            options
+                .headers
+                .iter()
+                .filter(|header| results.get(&Check::Header(header.to_string()).get_name()) == Some(&1))
+                .cloned(),
+        )
+        .collect()
+}
+
+fn generate_header(checks: &[Check], results: &BTreeMap<String, u64>, headers: &[String]) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "/* Generated by Ocean from the [checks] section of Ocean.toml. Do not edit. */").unwrap();
+    writeln!(out, "#ifndef OCEAN_CHECKS_H").unwrap();
+    writeln!(out, "#define OCEAN_CHECKS_H").unwrap();
+    writeln!(out).unwrap();
+
+    for check in checks {
+        match (check, results.get(&check.get_key(headers)).copied().unwrap_or(0)) {
+            (Check::TypeSize(_), size) => writeln!(out, "#define {} {}", check.get_macro(), size).unwrap(),
+            (_, 0) => writeln!(out, "/* #undef {} */", check.get_macro()).unwrap(),
+            _ => writeln!(out, "#define {} 1", check.get_macro()).unwrap(),
+        }
+    }
+
+    writeln!(out).unwrap();
+    writeln!(out, "#endif /* OCEAN_CHECKS_H */").unwrap();
+
+    out
+}
+
+fn load_cache(path: &str) -> BTreeMap<String, BTreeMap<String, u64>> {
+    read_to_string(path)
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+// Runs the checks in the [checks] section and returns the contents of
+// ocean_checks.h. Results are cached for each toolchain, identified by the
+// compiler, its version and the flags, so only new checks are run unless the
+// toolchain changes. Headers are checked first, since type sizes are checked
+// with every header that was found included.
+pub fn run(project: &Project, identity: &CompilerIdentity) -> Result<String, StrRet> {
+    let options = project.get_checks_options().unwrap_or_default();
+    let language = project.get_language();
+    let compiler = project.get_compiler().get_compiler_command(language).clone();
+
+    let mut flags = project.get_compiler().get_compiler_flags(language).clone();
+    flags.extend(project.get_include_dirs().iter().map(|dir| format!("-I{}", dir)));
+
+    let mut link_flags: Vec<String> = project.get_library_dirs().iter().map(|dir| format!("-L{}", dir)).collect();
+    link_flags.extend(project.get_libraries().iter().map(|library| format!("-l{}", library)));
+
+    let toolchain = Cache::hash_bytes(
+        [
+            compiler.as_str(),
+            &identity.family,
+            &identity.version,
+            &flags.join(" "),
+            &link_flags.join(" "),
+        ]
+        .join("\0")
+        .as_bytes(),
+    );
+
+    let directory = get_directory(project);
+    let probe_directory = Path::new(&directory).join("probes");
+    let cache_path = get_cache_path(project);
+    let mut cache = load_cache(&cache_path);
+    let mut results = cache.remove(&toolchain).unwrap_or_default();
+
+    let prober = Prober {
+        compiler,
+        extension: language.get_extension(),
+        flags,
+        link_flags,
+        directory: probe_directory.clone(),
+    };
+
+    let checks = get_checks(&options);
+    let (headers, others): (Vec<&Check>, Vec<&Check>) =
+        checks.iter().partition(|check| matches!(check, Check::Header(_)));
+
+    let mut ran = 0;
+
+    for group in [headers, others] {
+        let found_headers = get_found_headers(&options, &results);
+        let outdated: Vec<(usize, &Check)> = group
+            .into_iter()
+            .filter(|check| !results.contains_key(&check.get_key(&found_headers)))
+            .enumerate()
+            .collect();
+
+        if outdated.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = create_dir_all(&probe_directory) {
+            return Err(format!("Could not create {}: {}", probe_directory.display(), e).into());
+        }
+
+        let includes: String = found_headers
+            .iter()
+            .map(|header| format!("#include <{}>\n", header))
+            .collect();
+
+        let finished = run_parallel(outdated, get_job_count(), |(index, check)| {
+            (check, prober.run(index, check, &includes))
+        });
+
+        for (check, result) in finished {
+            let shown = match (check, result) {
+                (Check::TypeSize(_), 0) => "not found".to_string(),
+                (Check::TypeSize(_), size) => size.to_string(),
+                (_, 0) => "no".to_string(),
+                _ => "yes".to_string(),
+            };
+
+            println!("Checking {}... {}", check.get_name(), shown);
+
+            results.insert(check.get_key(&found_headers), result);
+            ran += 1;
+        }
+    }
+
+    remove_dir_all(&probe_directory).unwrap_or(());
+
+    if ran > 0 {
+        cache.insert(toolchain, results.clone());
+
+        let contents = match toml::to_string(&cache) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("Could not serialise {}: {}", cache_path, e).into()),
+        };
+
+        if let Err(e) = create_dir_all(&directory)
+            .and_then(|_| File::create(&cache_path))
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+        {
+            return Err(format!("Could not write {}: {}", cache_path, e).into());
+        }
+    }
+
+    Ok(generate_header(&checks, &results, &get_found_headers(&options, &results)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macro_names_are_upper_case_identifiers() {
+        assert_eq!(to_macro_name("sys/epoll.h"), "SYS_EPOLL_H");
+        assert_eq!(to_macro_name("void *"), "VOID_P");
+        assert_eq!(to_macro_name("unsigned long long"), "UNSIGNED_LONG_LONG");
+        assert_eq!(to_macro_name(" struct  timespec "), "STRUCT_TIMESPEC");
+        assert_eq!(to_macro_name("c++/cstdint"), "C_CSTDINT");
+    }
+
+    #[test]
+    fn header_defines_results() {
+        let checks = vec![
+            Check::Header("unistd.h".to_string()),
+            Check::Header("windows.h".to_string()),
+            Check::Function("strlcpy".to_string()),
+            Check::Symbol("MAP_ANONYMOUS".to_string(), "sys/mman.h".to_string()),
+            Check::TypeSize("void *".to_string()),
+            Check::TypeSize("__int128".to_string()),
+            Check::Library("m".to_string()),
+        ];
+        let headers = vec!["stddef.h".to_string(), "stdint.h".to_string(), "unistd.h".to_string()];
+        let results = BTreeMap::from([
+            ("header unistd.h".to_string(), 1),
+            ("header windows.h".to_string(), 0),
+            ("function strlcpy".to_string(), 0),
+            ("symbol MAP_ANONYMOUS in sys/mman.h".to_string(), 1),
+            ("size of void * with stddef.h, stdint.h, unistd.h".to_string(), 8),
+            ("size of __int128 with stddef.h, stdint.h".to_string(), 16),
+            ("library m".to_string(), 1),
+        ]);
+
+        assert_eq!(
+            generate_header(&checks, &results, &headers),
+            "/* Generated by Ocean from the [checks] section of Ocean.toml. Do not edit. */
+#ifndef OCEAN_CHECKS_H
+#define OCEAN_CHECKS_H
+
+#define HAVE_UNISTD_H 1
+/* #undef HAVE_WINDOWS_H */
+/* #undef HAVE_STRLCPY */
+#define HAVE_MAP_ANONYMOUS 1
+#define SIZEOF_VOID_P 8
+#define SIZEOF_INT128 0
+#define HAVE_LIBM 1
+
+#endif /* OCEAN_CHECKS_H */
+"
+        );
+    }
+
+    #[test]
+    fn type_sizes_are_cached_with_the_headers_found() {
+        let options = ChecksOptions {
+            headers: vec!["unistd.h".to_string(), "windows.h".to_string()],
+            ..ChecksOptions::default()
+        };
+        let mut results = BTreeMap::from([("header unistd.h".to_string(), 1), ("header windows.h".to_string(), 0)]);
+        let size = Check::TypeSize("long".to_string());
+        let key = size.get_key(&get_found_headers(&options, &results));
+
+        assert_eq!(key, "size of long with stddef.h, stdint.h, unistd.h");
+
+        results.insert("header windows.h".to_string(), 1);
+        assert_ne!(size.get_key(&get_found_headers(&options, &results)), key);
+    }
+}
@@ -1,11 +1,37 @@
-use crate::{cache::Cache, common::StrRet, editors::*, language::*, platform::*, project::*};
+use crate::{
+    assets::mirror_assets,
+    build_script::*,
+    cache::{Cache, Changes, FileResult, ResultCache},
+    checks,
+    common::*,
+    compiler::CompilerIdentity,
+    config_header,
+    coverage::Report,
+    deb,
+    editors::*,
+    install::{InstallFile, Installer},
+    language::*,
+    lint::parse_diagnostics,
+    package::{get_archive_mode, Tarball},
+    pkg_config,
+    platform::*,
+    project::*,
+    remote_cache::{self, RemoteCache},
+    resources::*,
+    shared_cache::{format_size, parse_size, SharedCache},
+};
 use std::{
+    collections::BTreeMap,
     env::{self, current_dir, set_current_dir},
     ffi::OsStr,
-    fs::{create_dir_all, read_dir, remove_dir_all, remove_file, rename, File},
+    fs::{create_dir_all, read, read_dir, read_to_string, remove_dir_all, remove_file, rename, File},
     io::prelude::*,
-    path::{Path, PathBuf},
-    process::Command,
+    net::TcpListener,
+    path::{Component, Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::Once,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
 };
 
 pub struct Commands;
@@ -51,7 +77,24 @@ impl Commands {
                     }
                 };
 
-                toml::from_str(contents.as_str()).unwrap()
+                let project: Project = match toml::from_str(contents.as_str()) {
+                    Ok(project) => project,
+                    Err(e) => return Err(format!("Could not parse Ocean.toml: {}", e).into()),
+                };
+
+                if let Err(e) = project.validate() {
+                    return Err(format!("Invalid Ocean.toml: {}", e).into());
+                }
+
+                // ocean watch reads Ocean.toml over and over, so only warn once.
+                static WARN_ONCE: Once = Once::new();
+                WARN_ONCE.call_once(|| {
+                    for warning in project.get_metadata_warnings() {
+                        eprintln!("Warning: Ocean.toml: {}", warning);
+                    }
+                });
+
+                project
             });
         }
 
@@ -59,8 +102,8 @@ impl Commands {
     }
 
     pub fn help(argument: Option<&String>) {
-        if argument.is_some() {
-            println!("Command \"{}\" not found.", argument.unwrap());
+        if let Some(argument) = argument {
+            println!("Command \"{}\" not found.", argument);
         }
         println!(
             "
@@ -69,12 +112,21 @@ Usage: ocean [OPTION]
 Create and manage C and C++ projects.
 
     build           Builds the current project
+    cache           Manages the shared object cache and serves it to other machines
+    check           Checks the current project for errors without producing objects or executables
     clean           Cleans the current project's build artifacts
+    coverage        Builds and runs the current project with coverage instrumentation and reports the results
+    fmt             Formats the current project's source files with clang-format
     get             Returns the values set in the Ocean.toml
-    set             Sets the values in side Ocean.toml
     help, --help    Shows this help text
+    install         Builds the current project in release mode and installs it
+    lint            Runs clang-tidy over the current project
     new             Creates a new C/C++ project in a new directory
+    package         Packages the current project's sources, and optionally its release build, into tarballs
     run             Runs the current project, builds if no build is present
+    set             Sets the values in side Ocean.toml
+    uninstall       Removes the files installed by install
+    watch           Rebuilds or reruns the current project whenever its files change
         "
         );
     }
@@ -83,15 +135,334 @@ Create and manage C and C++ projects.
         Path::new(filename).extension().and_then(OsStr::to_str)
     }
 
-    fn build_file(project: &Project, binary: &mut Binary, build_mode: &str) -> Result<(), StrRet> {
-        let executable_name = format!("{}{}", binary.name, env::consts::EXE_SUFFIX);
+    // Applies the --features, --no-default-features and --all-features
+    // options. Anything after -f or -- belongs to the compiler or the program.
+    fn select_features(project: &mut Project, args: &[String]) -> Result<(), StrRet> {
+        let mut requested = vec![];
+        let mut default_features = true;
+        let mut all_features = false;
+
+        let mut args = args
+            .iter()
+            .take_while(|arg| !["-f", "--flags", "--"].contains(&arg.as_str()));
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--features" => match args.next() {
+                    Some(features) => requested.extend(
+                        features
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|feature| !feature.is_empty())
+                            .map(String::from),
+                    ),
+                    None => return Err("Did not provide any features to --features".into()),
+                },
+                "--no-default-features" => default_features = false,
+                "--all-features" => all_features = true,
+                _ => (),
+            }
+        }
+
+        project.select_features(&requested, default_features, all_features)
+    }
+
+    fn get_compilable_files(project: &Project) -> Result<Vec<PathBuf>, StrRet> {
+        let file_extension = project.get_language().get_extension();
+        let mut compilable = vec![];
+
+        let source_files = match read_dir(project.get_directories().get_source_dir()) {
+            Ok(files) => files,
+            Err(e) => return Err(format!("Could not read source directory: {}", e).into()),
+        };
+
+        'a: for file in source_files {
+            let file_name = file.unwrap().path().clone();
+
+            if Path::new(&file_name).is_dir() {
+                continue;
+            }
+
+            for binary in project.get_binaries().into_iter() {
+                if binary.path == file_name {
+                    continue 'a;
+                }
+            }
+
+            if project.is_disabled_feature_source(&file_name) {
+                continue;
+            }
+
+            if Self::get_extension_from_filename(file_name.to_str().unwrap()) == Some(file_extension.as_str()) {
+                compilable.push(file_name);
+            }
+        }
+
+        for source in project.get_feature_sources() {
+            let source = PathBuf::from(source);
+
+            if !source.is_file() {
+                return Err(format!("Could not find feature source \"{}\"", source.display()).into());
+            }
+
+            if !compilable.iter().any(|file| file.ends_with(&source)) {
+                compilable.push(source);
+            }
+        }
+
+        Ok(compilable)
+    }
+
+    // Returns every translation unit of the project along with the compiler and
+    // flags that it is built with.
+    fn get_translation_units(
+        project: &Project,
+        build_mode: &str,
+        compiler_flags: &str,
+    ) -> Result<Vec<(PathBuf, String, Vec<String>)>, StrRet> {
+        let compiler = project.get_compiler().get_compiler_command(project.get_language());
+        let identity = CompilerIdentity::detect(compiler);
+
+        Self::write_config_header(project, build_mode, &identity)?;
+        Self::write_checks_header(project, &identity)?;
+
+        let flags: Vec<String> = Self::get_flags(project, build_mode, compiler_flags)?
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+
+        let mut units = vec![];
+
+        for file in Self::get_compilable_files(project)? {
+            units.push((file, compiler.clone(), flags.clone()));
+        }
+
+        for binary in project.get_binaries() {
+            let compiler = project.get_compiler().get_compiler_command(&binary.language);
+            let flags = Self::get_binary_flags(project, &binary, build_mode);
+
+            units.push((binary.path.clone(), compiler.clone(), flags));
+        }
+
+        if units.is_empty() {
+            return Err("No compilable files found.".into());
+        }
+
+        Ok(units)
+    }
+
+    // Runs the command of each (file, compiler, command) unit in parallel, reusing
+    // the result of any file whose contents, headers and command have not changed
+    // since the last run. `compiler` is the compiler and flags that the file is
+    // built with. Results are returned in the same order as `units`.
+    fn run_cached(
+        cache_path: &str,
+        units: Vec<(PathBuf, Vec<String>, Vec<String>)>,
+        jobs: usize,
+        save: bool,
+        verb: &str,
+    ) -> Result<Vec<FileResult>, StrRet> {
+        let cache = ResultCache::load(cache_path);
+
+        let mut results = vec![];
+        let mut outdated = vec![];
+
+        let keyed = run_parallel(units, jobs, |(path, compiler, command)| {
+            let hash = ResultCache::get_key(&path, &compiler, &command).unwrap_or_default();
+            (path, hash, command)
+        });
+
+        for (index, (path, hash, command)) in keyed.into_iter().enumerate() {
+            match cache.get(&path, &hash) {
+                Some(result) => results.push(Some(result.clone())),
+                None => {
+                    results.push(None);
+                    outdated.push((index, path, hash, command));
+                },
+            }
+        }
+
+        println!(
+            "{} {} file(s), {} unchanged...",
+            verb,
+            results.len(),
+            results.len() - outdated.len()
+        );
+
+        let finished = run_parallel(outdated, jobs, |(index, path, hash, command)| {
+            let result = match Command::new(&command[0]).args(&command[1..]).output() {
+                Ok(output) => FileResult {
+                    path,
+                    hash,
+                    success: output.status.success(),
+                    output: format!(
+                        "{}{}",
+                        String::from_utf8_lossy(&output.stdout),
+                        String::from_utf8_lossy(&output.stderr)
+                    ),
+                },
+                Err(e) => FileResult {
+                    path,
+                    hash,
+                    success: false,
+                    output: format!("Could not execute \"{}\": {}\n", command[0], e),
+                },
+            };
+
+            (index, result)
+        });
+
+        for (index, result) in finished {
+            results[index] = Some(result);
+        }
+
+        let results: Vec<FileResult> = results.into_iter().flatten().collect();
+
+        if save {
+            let mut new_cache = ResultCache::default();
+            for result in &results {
+                new_cache.insert(result.clone());
+            }
+
+            new_cache.save(cache_path)?;
+        }
+
+        Ok(results)
+    }
+
+    fn get_build_mode_flags(build_mode: &str) -> &'static str {
+        match build_mode {
+            "release" => "-Wall -Wextra -O3",
+            "coverage" => "-g -Wall -Wextra -O0 --coverage",
+            _ => "-g -ggdb -Wall -Wextra -Og",
+        }
+    }
+
+    fn get_flags(project: &Project, build_mode: &str, compiler_flags: &str) -> Result<String, StrRet> {
+        let lang_flags = if let Some(platform) = project.get_platform().clone() {
+            let default = project
+                .get_compiler()
+                .get_compiler_flags(project.get_language())
+                .clone();
+
+            match env::consts::OS {
+                "linux" =>
+                    if let Some(linux) = platform.linux {
+                        linux
+                            .get_compiler()
+                            .get_compiler_flags(project.get_language())
+                            .to_owned()
+                    } else {
+                        default
+                    },
+                "osx" =>
+                    if let Some(osx) = platform.osx {
+                        osx.get_compiler().get_compiler_flags(project.get_language()).to_owned()
+                    } else {
+                        default
+                    },
+                "windows" =>
+                    if let Some(windows) = platform.windows {
+                        windows
+                            .get_compiler()
+                            .get_compiler_flags(project.get_language())
+                            .to_owned()
+                    } else {
+                        default
+                    },
+                _ => return Err("Unsupported operating system".into()),
+            }
+            .join(" ")
+        } else {
+            project
+                .get_compiler()
+                .get_compiler_flags(project.get_language())
+                .join(" ")
+        };
+
+        let script_flags = match BuildScriptOutput::load(project, build_mode) {
+            Some(script_output) => script_output.get_compile_flags().join(" "),
+            None => String::new(),
+        };
+
+        let mut include_flags: Vec<String> = project
+            .get_include_dirs()
+            .iter()
+            .map(|dir| format!("-I{}", dir))
+            .collect();
+
+        include_flags.extend(project.get_feature_defines().iter().map(|define| format!("-D{}", define)));
+
+        if !project.get_resources().is_empty() {
+            include_flags.push(format!("-I{}", Resources::get_directory(project, build_mode)));
+        }
+
+        if project.get_config_header_options().is_some() {
+            include_flags.push(format!("-I{}", config_header::get_directory(project, build_mode)));
+        }
+
+        if project.get_checks_options().is_some() {
+            include_flags.push(format!("-I{}", checks::get_directory(project)));
+        }
+
+        if project.get_kind() == ProjectKind::Shared {
+            include_flags.push("-fPIC".to_string());
+        }
+
+        let include_flags = include_flags.join(" ");
+        let flag_arr: Vec<&str> = [
+            lang_flags.trim(),
+            script_flags.trim(),
+            include_flags.as_str(),
+            compiler_flags.trim(),
+        ]
+        .iter()
+        .filter(|flags| !flags.is_empty())
+        .copied()
+        .collect();
+        let extra_flags = flag_arr.join(" ");
+
+        Ok(if !extra_flags.is_empty() {
+            format!("{} {}", Self::get_build_mode_flags(build_mode), extra_flags)
+        } else {
+            Self::get_build_mode_flags(build_mode).to_string()
+        })
+    }
+
+    // Returns the flags that a binary in [[bins]] is compiled with, apart from
+    // the ones for linking. Check and lint use them too, so that they find the
+    // same headers as the build.
+    fn get_binary_flags(project: &Project, binary: &Binary, build_mode: &str) -> Vec<String> {
+        let mut flags: Vec<String> = Self::get_build_mode_flags(build_mode)
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+
+        flags.extend(binary.flags.iter().cloned());
 
-        let flags: String = match build_mode {
-            "release" => format!("-Wall -Wextra -O3 {}", binary.flags.join(" ")),
-            _ => format!("-g -ggdb -Wall -Wextra -Og {}", binary.flags.join(" ")),
+        if let Some(script_output) = BuildScriptOutput::load(project, build_mode) {
+            flags.extend(script_output.get_compile_flags());
         }
-        .trim()
-        .into();
+
+        flags.extend(project.get_feature_defines().iter().map(|define| format!("-D{}", define)));
+        flags.extend(project.get_include_dirs().iter().map(|dir| format!("-I{}", dir)));
+
+        if !project.get_resources().is_empty() {
+            flags.push(format!("-I{}", Resources::get_directory(project, build_mode)));
+        }
+
+        if project.get_config_header_options().is_some() {
+            flags.push(format!("-I{}", config_header::get_directory(project, build_mode)));
+        }
+
+        if project.get_checks_options().is_some() {
+            flags.push(format!("-I{}", checks::get_directory(project)));
+        }
+
+        flags
+    }
+
+    fn build_file(project: &Project, binary: &mut Binary, build_mode: &str) -> Result<(), StrRet> {
+        let executable_name = format!("{}{}", binary.name, env::consts::EXE_SUFFIX);
 
         let build_path = format!("{}/{}", project.get_directories().get_build_dir(), build_mode);
         if !Path::new(&build_path).exists() {
@@ -100,21 +471,29 @@ Create and manage C and C++ projects.
 
         let compiler_command = project.get_compiler().get_compiler_command(&binary.language);
 
+        let script_output = BuildScriptOutput::load(project, build_mode).unwrap_or_default();
+
         let mut command = Command::new(compiler_command);
-        command.args(flags.split(' '));
+        command.args(Self::get_binary_flags(project, binary, build_mode));
 
         for library_directory in project.get_library_dirs() {
             command.arg(format!("-L{}", library_directory));
         }
 
-        for library in project.get_libraries() {
+        for library in project.get_libraries().iter().chain(&project.get_feature_libraries()) {
             command.arg(format!("-l{}", library));
         }
 
+        command.arg(binary.path.clone());
+
+        if !project.get_resources().is_empty() {
+            command.arg(Resources::get_object_path(project, build_mode));
+        }
+
         command
-            .arg(binary.path.clone())
             .arg("-o")
-            .arg(format!("{}/{}", build_path, executable_name));
+            .arg(format!("{}/{}", build_path, executable_name))
+            .args(script_output.get_link_flags());
 
         println!(
             "Compiling {} to {}...",
@@ -122,8 +501,10 @@ Create and manage C and C++ projects.
             executable_name
         );
 
-        if let Err(e) = command.spawn().expect("Could not execute compiler").wait() {
-            return Err(format!("Could not compile file: {}", e).into());
+        match command.spawn().expect("Could not execute compiler").wait() {
+            Ok(status) if !status.success() => return Err(format!("Could not compile file: {}", status).into()),
+            Err(e) => return Err(format!("Could not compile file: {}", e).into()),
+            _ => (),
         }
 
         println!(
@@ -136,45 +517,13 @@ Create and manage C and C++ projects.
     }
 
     pub fn build(args: &[String]) -> Result<(), StrRet> {
-        let project = Self::get_toml(None, None)?;
+        let mut project = Self::get_toml(None, None)?;
 
         let mut build_mode = "debug";
         let mut is_verbose = false;
         let mut compiler_flags = String::from("");
         let mut bins = Vec::new();
 
-        let executable_name = format!("{}{}", project.get_name(), env::consts::EXE_SUFFIX);
-
-        let lock_file_path = "Ocean.lock";
-        let lock_file = Path::new(&lock_file_path);
-
-        let mut cache: Cache = if lock_file.exists() {
-            let mut lock_file = File::open(lock_file).expect("Could not open Ocean.lock");
-            let mut buffer = vec![];
-
-            lock_file.read_to_end(&mut buffer).expect("Could not read Ocean.lock");
-            toml::from_str(
-                String::from_utf8(buffer)
-                    .expect("Could not read Ocean.lock content as valid UTF-8")
-                    .as_str(),
-            )
-            .unwrap_or_else(|_| Cache::new(&project).unwrap())
-        } else {
-            Cache::new(&project)?
-        };
-
-        for directory in project.get_directories().get_all_dirs() {
-            if !Path::new(directory).exists() {
-                match create_dir_all(directory) {
-                    Err(e) => println!("Could not create directory \"{}\": {}", directory, e),
-                    _ => continue,
-                }
-            }
-        }
-
-        let file_extension = project.get_language().get_extension();
-        let compiler = project.get_compiler().get_compiler_command(project.get_language());
-
         for (index, arg) in args.iter().enumerate() {
             match arg.as_str() {
                 "--help" => {
@@ -187,7 +536,11 @@ By default, this builds projects in debug mode.
 Options:
     -d, --debug                 Builds the current project in debug mode (this is turned on by default).
     -r, --release               Builds the current project in release mode.
+    --coverage                  Builds the current project with coverage instrumentation.
     --bin [all, <bin_name>]     Builds a single file as a single executable.
+    --features [FEATURES]       Enables the given features, split by commas.
+    --no-default-features       Does not enable the default features.
+    --all-features              Enables every feature.
     -v, --verbose               Makes the compiler output verbose.
     -f, --flags                 Passes custom flags to the compiler.
             "
@@ -196,144 +549,676 @@ Options:
                 },
                 "-r" | "--release" => build_mode = "release",
                 "-d" | "--debug" => build_mode = "debug",
+                "--coverage" => build_mode = "coverage",
                 "--bin" => bins.push(
-                    if let Some(arg) = args.get(index as usize + 1) {
+                    if let Some(arg) = args.get(index + 1) {
                         arg
                     } else {
                         return Err("Did not provide binary name or all as paramater to --bin".into());
                     },
                 ),
                 "-v" | "--verbose" => is_verbose = true,
-                "-f" | "--flags" => compiler_flags = args[index as usize..].to_vec().join(" "),
+                "-f" | "--flags" => compiler_flags = args[index + 1..].to_vec().join(" "),
                 _ => (),
             }
         }
 
-        if !project.get_binaries().is_empty() && !bins.is_empty() {
-            if *bins[0] == "all" {
-                for binary in project.get_binaries().iter_mut() {
-                    Self::build_file(&project, binary, build_mode)?
-                }
+        Self::select_features(&mut project, args)?;
 
-                return Ok(());
-            } else {
-                for bin_name in bins.iter() {
-                    for binary in project.get_binaries().iter_mut() {
-                        if (*bin_name).clone() == binary.name {
-                            Self::build_file(&project, binary, build_mode)?
-                        } else {
-                            continue;
-                        }
+        let targets = if bins.is_empty() {
+            vec![project.get_name().clone()]
+        } else {
+            Self::get_executable_names(&project, &bins)
+        };
+
+        Self::run_hooks(&project, "pre_build", build_mode, &targets)?;
+        Self::build_project(&project, build_mode, is_verbose, &compiler_flags, &bins)?;
+        Self::copy_assets(&project, build_mode)?;
+        Self::run_hooks(&project, "post_build", build_mode, &targets)
+    }
+
+    pub fn cache(args: &[String]) -> Result<(), StrRet> {
+        let help = "
+Usage: ocean cache [COMMAND]
+
+Manages the object cache that is shared between projects. Projects use it when `shared = true` is set in the [cache] \
+                    section of their Ocean.toml.
+
+Commands:
+    stats                       Shows where the cache is, how large it is and how often it was used.
+    gc [--max-size SIZE]        Removes the least recently used objects until the cache is at most SIZE, such as \
+                    500M or 2G (default is 5G).
+    serve [OPTIONS]             Serves the objects of a cache directory over HTTP, for use as a remote cache.
+
+Serve options:
+    --address [ADDRESS]         Sets the address to listen on (default is 127.0.0.1:8080).
+    --dir [DIRECTORY]           Sets the directory to store objects in (default is the shared cache).
+        ";
+
+        let shared_cache = SharedCache::open();
+
+        match args.first().map(String::as_str) {
+            Some("stats") => shared_cache.print_stats(),
+            Some("gc") => {
+                let max_size = match args.iter().position(|arg| arg == "--max-size") {
+                    Some(index) => match args.get(index + 1) {
+                        Some(size) => parse_size(size)?,
+                        None => return Err("No size given to --max-size".into()),
+                    },
+                    None => 5 << 30,
+                };
+
+                let (removed, freed) = shared_cache.gc(max_size);
+                println!("Removed {} objects, freeing {}", removed, format_size(freed));
+            },
+            Some("serve") => {
+                let mut address = "127.0.0.1:8080";
+                let mut served_cache = shared_cache;
+
+                for (index, arg) in args.iter().enumerate() {
+                    match arg.as_str() {
+                        "--address" => match args.get(index + 1) {
+                            Some(value) => address = value,
+                            None => return Err("No address given to --address".into()),
+                        },
+                        "--dir" => match args.get(index + 1) {
+                            Some(value) => served_cache = SharedCache::at(PathBuf::from(value)),
+                            None => return Err("No directory given to --dir".into()),
+                        },
+                        _ => (),
                     }
                 }
-                return Ok(());
-            }
+
+                let listener = match TcpListener::bind(address) {
+                    Ok(listener) => listener,
+                    Err(e) => return Err(format!("Could not listen on {}: {}", address, e).into()),
+                };
+
+                println!("Serving the object cache on http://{}", address);
+                remote_cache::serve(listener, served_cache);
+            },
+            Some("--help") => println!("{}", help),
+            Some(command) => return Err(format!("Unknown cache command \"{}\"", command).into()),
+            None => {
+                println!("{}", help);
+                return Err("No cache command given".into());
+            },
         }
 
-        let build_path = format!("{}/{}", project.get_directories().get_build_dir(), build_mode);
-        let object_path = format!("{}/{}", project.get_directories().get_objects_dir(), build_mode);
+        Ok(())
+    }
 
-        let mut compilable = vec![];
+    fn copy_assets(project: &Project, build_mode: &str) -> Result<(), StrRet> {
+        let options = project.get_asset_options();
+        let manifest_path = format!(
+            "{}/{}/assets.list",
+            project.get_directories().get_objects_dir(),
+            build_mode
+        );
 
-        let source_files = read_dir(project.get_directories().get_source_dir().to_string()).unwrap();
-        'a: for file in source_files {
-            let file_name = file.unwrap().path().clone();
+        if options.paths.is_empty() && !Path::new(&manifest_path).exists() {
+            return Ok(());
+        }
 
-            if Path::new(&file_name).is_dir() {
-                continue;
-            }
+        if let Some(parent) = Path::new(&manifest_path).parent() {
+            create_dir_all(parent).unwrap_or(());
+        }
 
-            for binary in project.get_binaries().into_iter() {
-                if binary.path == file_name {
-                    continue 'a;
-                }
-            }
+        let build_path = format!("{}/{}", project.get_directories().get_build_dir(), build_mode);
+        let (updated, removed) =
+            mirror_assets(&options, Path::new("."), Path::new(&build_path), Path::new(&manifest_path))?;
 
-            if Self::get_extension_from_filename(file_name.to_str().unwrap()).unwrap() == file_extension {
-                compilable.push(file_name);
-            }
+        if updated > 0 {
+            println!("Copied {} asset(s) to {}", updated, build_path);
         }
 
-        if compilable.is_empty() {
-            return Err("No compilable files found.".into());
+        if removed > 0 {
+            println!("Removed {} stale asset(s) from {}", removed, build_path);
         }
 
-        if lock_file.exists() {
-            let changed = cache.get_changed(&project)?;
-            if !changed.is_empty() {
-                compilable = compilable
-                    .iter_mut()
-                    .enumerate()
-                    .filter(|(i, x)| *x == changed.get(*i - 1).unwrap_or(&PathBuf::new()))
-                    .map(|x| x.1.clone())
-                    .collect();
-            } else if !Path::new(&format!("{}/{}", build_path, executable_name)).exists() {
-                println!("Binary missing. Compiling anyway.");
-            } else {
-                println!("No compilation needed.");
-                return Ok(());
-            }
+        Ok(())
+    }
+
+    // Returns a command that starts the given executable, from the output
+    // directory if the project asks for that so relative asset paths resolve.
+    fn get_run_command(project: &Project, build_mode: &str, executable_name: &str) -> Command {
+        let build_path = format!("{}/{}", project.get_directories().get_build_dir(), build_mode);
+
+        if project.get_asset_options().run_in_output_dir {
+            let mut command = Command::new(get_absolute_path(&format!("{}/{}", build_path, executable_name)));
+            command.current_dir(build_path);
+            command
+        } else {
+            Command::new(format!("./{}/{}", build_path, executable_name))
         }
+    }
 
-        let lang_flags = if let Some(platform) = project.get_platform().clone() {
-            let default = project
-                .get_compiler()
-                .get_compiler_flags(project.get_language())
-                .clone();
+    // Returns the environment variables that describe a build to the commands
+    // that Ocean runs around it.
+    fn get_build_env(project: &Project, build_mode: &str, targets: &[String]) -> Vec<(String, String)> {
+        let root = current_dir().unwrap();
+        let absolute = |dir: &str| get_absolute_path(dir).join(build_mode).to_str().unwrap().to_string();
+
+        let build_path = absolute(project.get_directories().get_build_dir());
+        let object_path = absolute(project.get_directories().get_objects_dir());
+        let target_paths: Vec<String> = targets
+            .iter()
+            .map(|name| format!("{}/{}{}", build_path, name, env::consts::EXE_SUFFIX))
+            .collect();
+
+        vec![
+            ("OCEAN_PROJECT_NAME".to_string(), project.get_name().clone()),
+            ("OCEAN_PROJECT_ROOT".to_string(), root.to_str().unwrap().to_string()),
+            ("OCEAN_PROFILE".to_string(), build_mode.to_string()),
+            ("OCEAN_BUILD_DIR".to_string(), build_path),
+            ("OCEAN_OBJECT_DIR".to_string(), object_path),
+            ("OCEAN_TARGET".to_string(), target_paths.join(" ")),
+            ("OCEAN_TARGET_OS".to_string(), env::consts::OS.to_string()),
+            ("OCEAN_TARGET_ARCH".to_string(), env::consts::ARCH.to_string()),
+        ]
+    }
 
-            match env::consts::OS {
-                "linux" =>
-                    if let Some(linux) = platform.linux {
-                        linux
-                            .get_compiler()
-                            .get_compiler_flags(project.get_language())
-                            .to_owned()
-                    } else {
-                        default
-                    },
-                "osx" =>
-                    if let Some(osx) = platform.osx {
-                        osx.get_compiler().get_compiler_flags(project.get_language()).to_owned()
-                    } else {
-                        default
-                    },
-                "windows" =>
-                    if let Some(windows) = platform.windows {
-                        windows
-                            .get_compiler()
-                            .get_compiler_flags(project.get_language())
-                            .to_owned()
-                    } else {
-                        default
-                    },
-                _ => return Err("Unsupported operating system".into()),
+    fn run_hooks(project: &Project, hook: &str, build_mode: &str, targets: &[String]) -> Result<(), StrRet> {
+        let hooks = project.get_hooks();
+        let commands = match hook {
+            "pre_build" => hooks.pre_build,
+            "post_build" => hooks.post_build,
+            _ => hooks.pre_run,
+        };
+
+        for command in commands {
+            println!("Running {} hook: {}", hook, command);
+
+            let mut shell = if cfg!(windows) {
+                let mut shell = Command::new("cmd");
+                shell.arg("/C");
+                shell
+            } else {
+                let mut shell = Command::new("sh");
+                shell.arg("-c");
+                shell
+            };
+
+            match shell
+                .arg(&command)
+                .envs(Self::get_build_env(project, build_mode, targets))
+                .status()
+            {
+                Ok(status) if status.success() => (),
+                Ok(status) => return Err(format!("The {} hook \"{}\" failed with {}", hook, command, status).into()),
+                Err(e) => return Err(format!("Could not run the {} hook \"{}\": {}", hook, command, e).into()),
             }
-            .join(" ")
-        } else {
-            project
-                .get_compiler()
-                .get_compiler_flags(project.get_language())
-                .join(" ")
+        }
+
+        Ok(())
+    }
+
+    // Compiles and runs build.c if there is one and it is out of date. Returns
+    // whether the flags it produced changed, in which case everything has to be
+    // rebuilt.
+    fn run_build_script(project: &Project, build_mode: &str) -> Result<bool, StrRet> {
+        if !Path::new(BUILD_SCRIPT_PATH).exists() {
+            return Ok(false);
+        }
+
+        let script_hash = BuildScriptOutput::hash_path(Path::new(BUILD_SCRIPT_PATH));
+        let previous = BuildScriptOutput::load(project, build_mode);
+
+        if let Some(previous) = &previous {
+            if !previous.is_outdated(&script_hash) {
+                return Ok(false);
+            }
+        }
+
+        let directory = BuildScriptOutput::get_directory(project, build_mode);
+        let out_dir = get_absolute_path(&format!("{}/out", directory));
+        let executable = format!("{}/build{}", directory, env::consts::EXE_SUFFIX);
+
+        if let Err(e) = create_dir_all(&out_dir) {
+            return Err(format!("Could not create build script output directory: {}", e).into());
+        }
+
+        let recompile = match &previous {
+            Some(previous) => previous.script_hash != script_hash,
+            None => true,
         };
 
-        let flag_arr = [lang_flags.trim(), compiler_flags.trim()].join(" ");
-        let extra_flags = flag_arr.trim();
+        if recompile || !Path::new(&executable).exists() {
+            println!("Compiling build script {}...", BUILD_SCRIPT_PATH);
 
-        let flags = match build_mode {
-            "release" =>
-                if !extra_flags.is_empty() {
-                    format!("-Wall -Wextra -O3 {}", extra_flags)
-                } else {
-                    String::from("-Wall -Wextra -O3")
-                },
-            _ =>
-                if !extra_flags.is_empty() {
-                    format!("-g -ggdb -Wall -Wextra -Og {}", extra_flags)
-                } else {
-                    String::from("-g -ggdb -Wall -Wextra -Og")
+            match Command::new(project.get_compiler().get_compiler_command(&Language::C))
+                .arg(BUILD_SCRIPT_PATH)
+                .arg("-o")
+                .arg(&executable)
+                .status()
+            {
+                Ok(status) if status.success() => (),
+                Ok(status) => return Err(format!("Could not compile build script: {}", status).into()),
+                Err(e) => return Err(format!("Could not compile build script: {}", e).into()),
+            }
+        }
+
+        println!("Running build script...");
+
+        let output = match Command::new(format!("./{}", executable))
+            .envs(Self::get_build_env(project, build_mode, &[project.get_name().clone()]))
+            .env("OCEAN_OUT_DIR", &out_dir)
+            .stderr(Stdio::inherit())
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => return Err(format!("Build script failed with {}", output.status).into()),
+            Err(e) => return Err(format!("Could not run build script: {}", e).into()),
+        };
+
+        let (script_output, warnings) =
+            BuildScriptOutput::parse(&String::from_utf8_lossy(&output.stdout), script_hash)?;
+
+        for warning in warnings {
+            println!("Warning (build script): {}", warning);
+        }
+
+        let changed = match &previous {
+            Some(previous) =>
+                previous.get_compile_flags() != script_output.get_compile_flags()
+                    || previous.get_link_flags() != script_output.get_link_flags(),
+            None => true,
+        };
+
+        script_output.save(project, build_mode)?;
+
+        Ok(changed)
+    }
+
+    // Generates and compiles the sources for the [resources] section if any
+    // resource changed. Returns whether the object was rebuilt, and whether the
+    // generated header changed, in which case everything has to be rebuilt.
+    fn build_resources(project: &Project, build_mode: &str) -> Result<(bool, bool), StrRet> {
+        let resources = Resources::collect(project)?;
+
+        if resources.is_empty() {
+            return Ok((false, false));
+        }
+
+        // The resources are plain C, so they get the flags of [compiler.c] rather
+        // than those of the project's language.
+        let mut flags: Vec<String> = Self::get_build_mode_flags(build_mode)
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        flags.extend(project.get_compiler().get_compiler_flags(&Language::C).iter().cloned());
+
+        if project.get_kind() == ProjectKind::Shared {
+            flags.push("-fPIC".to_string());
+        }
+
+        let hash = Cache::hash_bytes(format!("{}\0{}", resources.get_hash(), flags.join(" ")).as_bytes());
+        let hash_path = Resources::get_hash_path(project, build_mode);
+        let object_path = Resources::get_object_path(project, build_mode);
+
+        if read_to_string(&hash_path).ok().as_deref() == Some(hash.as_str()) && Path::new(&object_path).exists() {
+            return Ok((false, false));
+        }
+
+        if let Err(e) = create_dir_all(Resources::get_directory(project, build_mode)) {
+            return Err(format!("Could not create resources directory: {}", e).into());
+        }
+
+        let header_path = Resources::get_header_path(project, build_mode);
+        let source_path = Resources::get_source_path(project, build_mode);
+        let header = resources.to_header();
+        let header_changed = read_to_string(&header_path).ok() != Some(header.clone());
+
+        if let Err(e) = File::create(&header_path).and_then(|mut file| file.write_all(header.as_bytes())) {
+            return Err(format!("Could not write {}: {}", header_path, e).into());
+        }
+
+        if let Err(e) = File::create(&source_path).and_then(|mut file| file.write_all(resources.to_source().as_bytes()))
+        {
+            return Err(format!("Could not write {}: {}", source_path, e).into());
+        }
+
+        println!("Compiling {} resources...", resources.resources.len());
+
+        match Command::new(project.get_compiler().get_compiler_command(&Language::C))
+            .args(&flags)
+            .arg("-c")
+            .arg(&source_path)
+            .arg("-o")
+            .arg(&object_path)
+            .status()
+        {
+            Ok(status) if status.success() => (),
+            Ok(status) => return Err(format!("Could not compile resources: {}", status).into()),
+            Err(e) => return Err(format!("Could not compile resources: {}", e).into()),
+        }
+
+        if let Err(e) = File::create(&hash_path).and_then(|mut file| file.write_all(hash.as_bytes())) {
+            return Err(format!("Could not write {}: {}", hash_path, e).into());
+        }
+
+        Ok((true, header_changed))
+    }
+
+    // Writes ocean_config.h if the project asks for it and its contents changed.
+    // The sources that include it are recompiled through their dependency
+    // files, which also notice when check or lint rewrote it.
+    fn write_config_header(project: &Project, build_mode: &str, identity: &CompilerIdentity) -> Result<(), StrRet> {
+        if project.get_config_header_options().is_none() {
+            return Ok(());
+        }
+
+        let header_path = config_header::get_header_path(project, build_mode);
+        let header = config_header::generate(project, build_mode, identity);
+
+        if read_to_string(&header_path).ok().as_deref() == Some(header.as_str()) {
+            return Ok(());
+        }
+
+        if let Err(e) = create_dir_all(config_header::get_directory(project, build_mode)) {
+            return Err(format!("Could not create config header directory: {}", e).into());
+        }
+
+        if let Err(e) = File::create(&header_path).and_then(|mut file| file.write_all(header.as_bytes())) {
+            return Err(format!("Could not write {}: {}", header_path, e).into());
+        }
+
+        Ok(())
+    }
+
+    // Runs the configuration checks and writes ocean_checks.h if its contents
+    // changed. The header is shared by every build mode, so the sources that
+    // include it are recompiled through their dependency files rather than
+    // when this rewrites it.
+    fn write_checks_header(project: &Project, identity: &CompilerIdentity) -> Result<(), StrRet> {
+        if project.get_checks_options().is_none() {
+            return Ok(());
+        }
+
+        let header_path = checks::get_header_path(project);
+        let header = checks::run(project, identity)?;
+
+        if read_to_string(&header_path).ok().as_deref() == Some(header.as_str()) {
+            return Ok(());
+        }
+
+        if let Err(e) = create_dir_all(checks::get_directory(project)) {
+            return Err(format!("Could not create checks directory: {}", e).into());
+        }
+
+        if let Err(e) = File::create(&header_path).and_then(|mut file| file.write_all(header.as_bytes())) {
+            return Err(format!("Could not write {}: {}", header_path, e).into());
+        }
+
+        Ok(())
+    }
+
+    // Returns the path of the object that `file` compiles to. Files directly in
+    // the source directory are named after their stem, and any others, such as
+    // feature sources elsewhere in the project, mirror their path below
+    // by-path/, so that files with the same name do not share an object.
+    // Sources outside the project, whose path has a ".." in it or is absolute,
+    // mirror their absolute path below by-absolute-path/ instead, so that their
+    // objects cannot end up outside the object directory.
+    fn get_object_path(project: &Project, object_path: &str, file: &Path) -> String {
+        let normalise = |path: &Path| -> PathBuf {
+            path.components()
+                .filter(|component| *component != Component::CurDir)
+                .collect()
+        };
+        let file = normalise(file);
+
+        if file.parent() == Some(&normalise(Path::new(project.get_directories().get_source_dir()))) {
+            return format!("{}/{}.o", object_path, file.file_stem().unwrap().to_str().unwrap());
+        }
+
+        if file.components().all(|component| matches!(component, Component::Normal(_))) {
+            return format!("{}/by-path/{}", object_path, file.with_extension("o").to_str().unwrap());
+        }
+
+        let mut absolute = PathBuf::new();
+
+        for component in current_dir().unwrap_or_default().join(&file).components() {
+            match component {
+                Component::Prefix(prefix) => absolute.push(prefix.as_os_str().to_string_lossy().replace(':', "")),
+                Component::ParentDir => {
+                    absolute.pop();
                 },
+                Component::Normal(name) => absolute.push(name),
+                Component::RootDir | Component::CurDir => (),
+            }
+        }
+
+        format!(
+            "{}/by-absolute-path/{}",
+            object_path,
+            absolute.with_extension("o").to_str().unwrap()
+        )
+    }
+
+    // Deletes the objects, dependency files and coverage files of sources that
+    // were deleted, renamed or belong to features that were turned off, along
+    // with the temporary files of interrupted compiles.
+    fn remove_stale_objects(cache: &mut Cache, object_path: &str, object_files: &[String]) {
+        let mut files: Vec<PathBuf> = read_dir(object_path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|file| file.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.extend(get_files_recursive(&Path::new(object_path).join("by-path")));
+        files.extend(get_files_recursive(&Path::new(object_path).join("by-absolute-path")));
+
+        for path in files {
+            let extension = path.extension().and_then(OsStr::to_str);
+            let object = path.with_extension("o");
+
+            if extension == Some("tmp") {
+                remove_file(&path).unwrap_or(());
+                continue;
+            }
+
+            if !matches!(extension, Some("o" | "d" | "gcno" | "gcda"))
+                || object_files.iter().any(|file| Path::new(file) == object)
+            {
+                continue;
+            }
+
+            if remove_file(&path).is_ok() && extension == Some("o") {
+                println!("Removed {}", object.display());
+            }
+
+            cache.remove_object_fingerprint(object.to_str().unwrap());
+        }
+    }
+
+    fn get_link_command(
+        project: &Project,
+        build_mode: &str,
+        is_verbose: bool,
+        object_files: &[String],
+        output_path: &str,
+    ) -> Command {
+        if project.get_kind() == ProjectKind::Static {
+            let mut c = Command::new(project.get_compiler().get_archiver());
+            c.arg("rcs").arg(output_path).args(object_files);
+            return c;
+        }
+
+        let mut c = Command::new(project.get_compiler().get_compiler_command(project.get_language()));
+
+        if is_verbose {
+            c.arg("-v");
+        }
+
+        if project.get_kind() == ProjectKind::Shared {
+            c.arg("-shared");
+        }
+
+        c.args(object_files);
+
+        if build_mode == "coverage" {
+            c.arg("--coverage");
+        }
+
+        c.arg("-o").arg(output_path);
+
+        for library_directory in project.get_library_dirs() {
+            c.arg(format!("-L{}", library_directory));
+        }
+
+        for library in project.get_libraries().iter().chain(&project.get_feature_libraries()) {
+            c.arg(format!("-l{}", library));
+        }
+
+        if let Some(script_output) = BuildScriptOutput::load(project, build_mode) {
+            c.args(script_output.get_link_flags());
+        }
+
+        c
+    }
+
+    fn build_project(
+        project: &Project,
+        build_mode: &str,
+        is_verbose: bool,
+        compiler_flags: &str,
+        bins: &[&String],
+    ) -> Result<(), StrRet> {
+        let executable_name = project.get_output_name();
+
+        Cache::remove_legacy_lock_file();
+
+        let previous_cache = Cache::load(project, build_mode);
+        let has_cache = previous_cache.is_some();
+        let mut cache = match previous_cache {
+            Some(cache) => cache,
+            None => Cache::new(project)?,
+        };
+
+        for directory in project.get_directories().get_all_dirs() {
+            if !Path::new(directory).exists() {
+                match create_dir_all(directory) {
+                    Err(e) => println!("Could not create directory \"{}\": {}", directory, e),
+                    _ => continue,
+                }
+            }
+        }
+
+        // The compiler is only asked what it is once per build, as the checks
+        // and the config header need to know as well.
+        let compiler = project.get_compiler().get_compiler_command(project.get_language());
+        let identity = CompilerIdentity::detect(compiler);
+        let script_changed = Self::run_build_script(project, build_mode)?;
+        let (resources_changed, resource_header_changed) = Self::build_resources(project, build_mode)?;
+        Self::write_config_header(project, build_mode, &identity)?;
+        Self::write_checks_header(project, &identity)?;
+        let features: Vec<String> = project.get_enabled_features().into_iter().collect();
+        let features_changed = has_cache && *cache.get_features() != features;
+
+        if !project.get_binaries().is_empty() && !bins.is_empty() {
+            if *bins[0] == "all" {
+                for binary in project.get_binaries().iter_mut() {
+                    Self::build_file(project, binary, build_mode)?
+                }
+
+                return Ok(());
+            } else {
+                for bin_name in bins.iter() {
+                    for binary in project.get_binaries().iter_mut() {
+                        if (*bin_name).clone() == binary.name {
+                            Self::build_file(project, binary, build_mode)?
+                        } else {
+                            continue;
+                        }
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        let build_path = format!("{}/{}", project.get_directories().get_build_dir(), build_mode);
+        let object_path = format!("{}/{}", project.get_directories().get_objects_dir(), build_mode);
+        let output_path = format!("{}/{}", build_path, executable_name);
+
+        let mut compilable = Self::get_compilable_files(project)?;
+
+        if compilable.is_empty() {
+            return Err("No compilable files found.".into());
+        }
+
+        let flags = Self::get_flags(project, build_mode, compiler_flags)?;
+        let get_object = |file: &Path| Self::get_object_path(project, &object_path, file);
+        let get_dependency_file = |file: &Path| Path::new(&get_object(file)).with_extension("d");
+        let get_compile_command = |file: &Path| {
+            let mut c = Command::new(compiler);
+            c.args(flags.split(' ')).arg("-c").arg(file);
+            c
+        };
+
+        // Only the objects of the current sources are linked, so that objects
+        // left behind by deleted or renamed sources cannot clash with them.
+        let mut object_files: Vec<String> = compilable.iter().map(|file| get_object(file)).collect();
+        object_files.sort();
+        object_files.dedup();
+
+        if !project.get_resources().is_empty() {
+            object_files.push(Resources::get_object_path(project, build_mode));
+        }
+
+        let get_link_fingerprint = || {
+            Cache::get_link_fingerprint(
+                &Self::get_link_command(project, build_mode, false, &object_files, &output_path),
+                &identity,
+                &object_files,
+            )
+        };
+
+        // The headers that each file includes are hashed along with its compile
+        // command, so that changing one recompiles the files that include it.
+        let changes = if has_cache {
+            cache.get_changed(project)?
+        } else {
+            Changes::default()
         };
 
+        if script_changed {
+            println!("Build script output changed. Compiling everything.");
+        } else if resource_header_changed {
+            println!("Resources changed. Compiling everything.");
+        } else if features_changed {
+            println!("Features changed. Compiling everything.");
+        } else if has_cache {
+            compilable.retain(|file| {
+                let object = get_object(file);
+                let fingerprint = cache.add_dependencies(
+                    &Cache::get_fingerprint(&get_compile_command(file), &identity),
+                    &get_dependency_file(file),
+                );
+
+                changes.needs_compiling(file)
+                    || !Path::new(&object).exists()
+                    || fingerprint.is_none()
+                    || cache.get_object_fingerprint(&object) != fingerprint.as_ref()
+            });
+
+            if changes.is_empty() && compilable.is_empty() {
+                if !Path::new(&output_path).exists() {
+                    println!("Binary missing. Compiling anyway.");
+                } else if resources_changed {
+                    println!("Resources changed. Relinking.");
+                } else if cache.get_output_fingerprint(&output_path) != Some(&get_link_fingerprint()) {
+                    println!("Link command changed. Relinking.");
+                } else {
+                    println!("No compilation needed.");
+                    return Ok(());
+                }
+            }
+        }
+
+        Self::remove_stale_objects(&mut cache, &object_path, &object_files);
+
         if !Path::new(&object_path).exists() {
             if let Err(e) = create_dir_all(object_path.clone()) {
                 println!("Could not create directory \"{}\": {}", object_path, e);
@@ -346,182 +1231,1004 @@ Options:
             }
         }
 
-        let mut object_files = vec![];
+        // Coverage notes are written next to the object, so coverage builds do
+        // not use the shared cache. OCEAN_CACHE_REMOTE lets CI use a remote
+        // cache without changing Ocean.toml.
+        let cache_options = project.get_cache_options();
+        let remote = env::var("OCEAN_CACHE_REMOTE")
+            .ok()
+            .filter(|remote| !remote.is_empty())
+            .or(cache_options.remote);
+
+        let shared_cache = if build_mode == "coverage" {
+            None
+        } else if let Some(remote) = remote {
+            Some(SharedCache::open().with_remote(RemoteCache::new(&remote)?))
+        } else if cache_options.shared {
+            Some(SharedCache::open())
+        } else {
+            None
+        };
+        let (mut hits, mut misses) = (0, 0);
+
+        // Objects are only trusted once they have been written completely, so
+        // the fingerprints of the ones about to be replaced are dropped first,
+        // in case the build is interrupted.
+        if !compilable.is_empty() {
+            for file in &compilable {
+                cache.remove_object_fingerprint(&get_object(file));
+            }
+
+            cache.save(project, build_mode)?;
+        }
 
         for file in compilable {
+            let mut c = get_compile_command(&file);
+            let fingerprint = Cache::get_fingerprint(&c, &identity);
+            let dependency_file = get_dependency_file(&file);
+
+            // The dependency file is left out of the fingerprint, as its path
+            // differs between projects and build modes. Working out the shared
+            // cache key writes it too, so restored objects get one as well. An
+            // object whose dependency file cannot be read gets an empty
+            // fingerprint, so that it is compiled again by the next build.
+            c.arg("-MMD").arg("-MF").arg(&dependency_file);
+
+            let shared_key = shared_cache.as_ref().and_then(|_| SharedCache::get_key(&c, &fingerprint));
+            let object = get_object(&file);
+
+            if let Some(parent) = Path::new(&object).parent() {
+                create_dir_all(parent).unwrap_or(());
+            }
+
+            if let (Some(shared_cache), Some(key)) = (&shared_cache, &shared_key) {
+                if shared_cache.restore(key, Path::new(&object)) {
+                    println!(
+                        "Restored {}.o from the shared cache",
+                        file.file_stem().unwrap().to_str().unwrap()
+                    );
+
+                    let fingerprint = cache.add_dependencies(&fingerprint, &dependency_file).unwrap_or_default();
+                    cache.set_object_fingerprint(object, fingerprint);
+                    hits += 1;
+                    continue;
+                }
+            }
+
             println!(
                 "Compiling {} to {}.o...",
                 file.file_name().unwrap().to_str().unwrap(),
                 file.file_stem().unwrap().to_str().unwrap()
             );
 
-            let mut c = Command::new(compiler.clone());
+            // The compiler writes to a temporary file that replaces the object
+            // once it is complete. Coverage notes and counters are named after
+            // the file the compiler writes, so coverage objects are written in
+            // place instead.
+            let output = if build_mode == "coverage" {
+                object.clone()
+            } else {
+                format!("{}.tmp", object)
+            };
+
+            c.arg("-o").arg(&output);
 
             if is_verbose {
                 c.arg("-v");
             }
 
-            if let Err(e) = c
-                .args(&flags.split(' ').collect::<Vec<&str>>())
-                .arg("-c")
-                .arg(file.to_str().unwrap())
-                .spawn()
-                .expect("Could not execute compiler")
-                .wait()
-            {
-                return Err(format!("Compiler command returned with error code: {}", e).into());
+            let status = c.spawn().expect("Could not execute compiler").wait();
+            if !matches!(status, Ok(status) if status.success()) {
+                remove_file(&output).unwrap_or(());
+            }
+
+            match status {
+                Ok(status) if !status.success() =>
+                    return Err(format!("Compiler command returned with error code: {}", status).into()),
+                Err(e) => return Err(format!("Compiler command returned with error code: {}", e).into()),
+                _ => (),
             };
 
-            if let Err(e) = rename(
-                format!("{}.o", file.file_stem().unwrap().to_str().unwrap()),
-                format!("{}/{}.o", object_path, file.file_stem().unwrap().to_str().unwrap()),
-            ) {
-                return Err(format!("Cannot move object file: {}. Did the project compile properly?", e).into());
-            }
+            if output != object {
+                if let Err(e) = rename(&output, &object) {
+                    return Err(format!("Cannot move object file: {}. Did the project compile properly?", e).into());
+                }
+            }
+
+            if let (Some(shared_cache), Some(key)) = (&shared_cache, &shared_key) {
+                shared_cache.store(key, Path::new(&object));
+                misses += 1;
+            }
+
+            let fingerprint = cache.add_dependencies(&fingerprint, &dependency_file).unwrap_or_default();
+            cache.set_object_fingerprint(object, fingerprint);
+
+            println!("Compiled {}.o", file.file_stem().unwrap().to_str().unwrap());
+        }
+
+        if let Some(shared_cache) = &shared_cache {
+            if hits + misses > 0 {
+                shared_cache.record(hits, misses);
+            }
+        }
+
+        // Recompiling a file does not always change its object, such as when
+        // only a comment changed, in which case there is nothing to link.
+        let link_fingerprint = get_link_fingerprint();
+
+        if Path::new(&output_path).exists() && cache.get_output_fingerprint(&output_path) == Some(&link_fingerprint) {
+            println!("No linking needed.");
+        } else {
+            // ar only adds and replaces members, so start from an empty archive
+            // to drop the objects of deleted sources.
+            if project.get_kind() == ProjectKind::Static {
+                remove_file(&output_path).unwrap_or(());
+            }
+
+            let mut c = Self::get_link_command(project, build_mode, is_verbose, &object_files, &output_path);
+
+            match c.status() {
+                Ok(status) if !status.success() =>
+                    return Err(format!("Compiler command returned with error code: {}", status).into()),
+                Err(e) =>
+                    return Err(format!("Could not execute \"{}\": {}", c.get_program().to_string_lossy(), e).into()),
+                _ => (),
+            };
+
+            cache.set_output_fingerprint(output_path, link_fingerprint);
+        }
+
+        cache.update_cache(project)?;
+        cache.save(project, build_mode)
+    }
+
+    pub fn run(args: &[String]) -> Result<(), StrRet> {
+        let mut build_mode = "debug";
+        let mut program_args = vec![];
+        let mut bins = vec![];
+
+        for (index, arg) in args.iter().enumerate() {
+            match arg.as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean run [OPTIONS]
+
+By default, this run projects in debug mode.
+
+Options:
+    -d, --debug                 Runs the current project in debug mode (this is turned on by default)
+    -r, --release               Runs the current project in release mode
+    --bin [all, <bin_name>]     Builds a single file as a single executable.
+    --features [FEATURES]       Enables the given features, split by commas.
+    --no-default-features       Does not enable the default features.
+    --all-features              Enables every feature.
+    -v, --verbose               Makes the compiler output verbose.
+    -f, --flags                 Passes custom flags to the compiler.
+            "
+                    );
+                    return Ok(());
+                },
+                "-r" | "--release" => build_mode = "release",
+                "-d" | "--debug" => build_mode = "debug",
+                "--bin" => bins.push(
+                    if let Some(arg) = args.get(index + 1) {
+                        arg
+                    } else {
+                        return Err("Did not provide binary name or all as paramater to --bin".into());
+                    },
+                ),
+                "--" => program_args = args[index + 1..].to_vec(),
+                _ => (), // -v and -f are handled by the build function
+            }
+        }
+
+        Self::build(args)?;
+
+        let project = Self::get_toml(None, None)?;
+
+        if bins.is_empty() && project.get_kind() != ProjectKind::Executable {
+            return Err(format!(
+                "\"{}\" is a {} library and cannot be run",
+                project.get_name(),
+                project.get_kind()
+            )
+            .into());
+        }
+
+        let names = Self::get_executable_names(&project, &bins);
+
+        Self::run_hooks(&project, "pre_run", build_mode, &names)?;
+
+        let run = |name: String, program_args: &Vec<String>| -> Result<(), StrRet> {
+            let executable_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+            let executable_path = format!(
+                "{}/{}/{}",
+                project.get_directories().get_build_dir(),
+                build_mode,
+                executable_name
+            );
+
+            if Path::new(&executable_path).exists() {
+                println!("\n[Running '{}']", executable_name);
+                Self::get_run_command(&project, build_mode, &executable_name)
+                    .args(program_args)
+                    .spawn()
+                    .expect("Could not start application")
+                    .wait()
+                    .expect("Application exited unexpectedly");
+
+                Ok(())
+            } else {
+                Err(format!(
+                    "Cannot find the \"{}\" executable. Did it compile properly?",
+                    executable_name
+                )
+                .into())
+            }
+        };
+
+        for name in names {
+            run(name, &program_args)?;
+        }
+
+        Ok(())
+    }
+
+    // Returns the names of the executables selected by the --bin arguments, or
+    // the project's own executable if there were none.
+    fn get_executable_names(project: &Project, bins: &[&String]) -> Vec<String> {
+        if bins.is_empty() {
+            return vec![project.get_name().clone()];
+        }
+
+        project
+            .get_binaries()
+            .into_iter()
+            .filter(|binary| *bins[0] == "all" || bins.contains(&&binary.name))
+            .map(|binary| binary.name)
+            .collect()
+    }
+
+    // Returns the modification time and size of every file that should trigger
+    // a rebuild when it changes.
+    fn get_watched_files(project: &Project) -> BTreeMap<PathBuf, (Option<SystemTime>, u64)> {
+        let mut paths = vec![
+            PathBuf::from("Ocean.toml"),
+            PathBuf::from(BUILD_SCRIPT_PATH),
+            PathBuf::from(project.get_directories().get_source_dir()),
+        ];
+
+        paths.extend(project.get_include_dirs().iter().map(PathBuf::from));
+
+        let mut flags = vec![];
+        flags.extend(project.get_compiler().get_compiler_flags(&Language::C).iter().cloned());
+        flags.extend(
+            project
+                .get_compiler()
+                .get_compiler_flags(&Language::CXX)
+                .iter()
+                .cloned(),
+        );
+
+        for binary in project.get_binaries() {
+            paths.push(binary.path);
+            flags.extend(binary.flags);
+        }
+
+        paths.extend(project.get_asset_options().paths.iter().map(PathBuf::from));
+
+        for pattern in project.get_resources().values() {
+            if let Ok(matches) = glob::glob(pattern) {
+                paths.extend(matches.flatten());
+            }
+        }
+
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            if flag == "-I" {
+                if let Some(directory) = flags.next() {
+                    paths.push(PathBuf::from(directory));
+                }
+            } else if let Some(directory) = flag.strip_prefix("-I") {
+                paths.push(PathBuf::from(directory));
+            }
+        }
+
+        let mut files = BTreeMap::new();
+
+        for path in paths {
+            for file in get_files_recursive(&path) {
+                if let Ok(metadata) = file.metadata() {
+                    files.insert(file, (metadata.modified().ok(), metadata.len()));
+                }
+            }
+        }
+
+        files
+    }
+
+    pub fn watch(args: &[String]) -> Result<(), StrRet> {
+        let help = "
+Usage: ocean watch [build, run, check] [OPTIONS] [-- ARGS]
+
+Watches the source directory, include directories and Ocean.toml, and rebuilds the current project when they change. \
+                    With run, the program is restarted after every successful build. The options are the same as the \
+                    ones for the build, run and check commands.
+
+Options:
+    --debounce [MILLISECONDS]   Sets how long to wait for changes to settle before rebuilding (default is 300).
+";
+
+        let (action, args) = match args.first().map(String::as_str) {
+            Some("--help") => {
+                println!("{}", help);
+                return Ok(());
+            },
+            Some(action @ "build") | Some(action @ "run") | Some(action @ "check") => (action, &args[1..]),
+            Some(action) if !action.starts_with('-') => {
+                println!("{}", help);
+                return Err(format!("Cannot watch \"{}\"", action).into());
+            },
+            _ => ("build", args),
+        };
+
+        let (args, program_args) = match args.iter().position(|arg| arg == "--") {
+            Some(index) => (&args[..index], &args[index + 1..]),
+            None => (args, &args[args.len()..]),
+        };
+
+        let mut debounce = Duration::from_millis(300);
+        let mut build_args = vec![];
+        let mut bins = vec![];
+        let mut build_mode = "debug";
+
+        let mut index = 0;
+        while index < args.len() {
+            match args[index].as_str() {
+                "--debounce" => {
+                    index += 1;
+                    debounce = match args.get(index).map(|d| d.parse()) {
+                        Some(Ok(milliseconds)) => Duration::from_millis(milliseconds),
+                        _ => return Err("Did not provide a valid number of milliseconds to --debounce".into()),
+                    };
+                },
+                arg => {
+                    match arg {
+                        "-r" | "--release" => build_mode = "release",
+                        "-d" | "--debug" => build_mode = "debug",
+                        "--bin" =>
+                            if let Some(name) = args.get(index + 1) {
+                                bins.push(name);
+                            },
+                        _ => (),
+                    }
+
+                    build_args.push(arg.to_string());
+                },
+            }
+
+            index += 1;
+        }
+
+        let mut snapshot = Self::get_watched_files(&Self::get_toml(None, None)?);
+        let mut children: Vec<Child> = vec![];
+        let mut pending = true;
+        // The first build starts straight away, as there is nothing to wait for.
+        let mut last_change: Option<Instant> = None;
+
+        loop {
+            if pending && last_change.is_none_or(|time| time.elapsed() >= debounce) {
+                pending = false;
+
+                let result = match action {
+                    "check" => Self::check(&build_args),
+                    _ => Self::build(&build_args),
+                };
+
+                match result {
+                    Err(e) => eprintln!("Error: {}", e),
+                    Ok(_) if action == "run" => {
+                        for mut child in children.drain(..) {
+                            child.kill().unwrap_or(());
+                            child.wait().ok();
+                        }
+
+                        let project = Self::get_toml(None, None)?;
+                        let names = Self::get_executable_names(&project, &bins);
+
+                        if let Err(e) = Self::run_hooks(&project, "pre_run", build_mode, &names) {
+                            eprintln!("Error: {}", e);
+                            println!("\n[Watching for changes]");
+                            continue;
+                        }
+
+                        for name in names {
+                            let executable_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+                            println!("\n[Running '{}']", executable_name);
+
+                            match Self::get_run_command(&project, build_mode, &executable_name)
+                                .args(program_args)
+                                .spawn()
+                            {
+                                Ok(child) => children.push(child),
+                                Err(e) => eprintln!("Could not start '{}': {}", executable_name, e),
+                            }
+                        }
+                    },
+                    _ => (),
+                }
+
+                println!("\n[Watching for changes]");
+            }
+
+            children.retain_mut(|child| match child.try_wait() {
+                Ok(Some(status)) => {
+                    println!("\n[Program exited with {}]", status);
+                    false
+                },
+                _ => true,
+            });
+
+            sleep(Duration::from_millis(100));
+
+            // Ocean.toml may be halfway through being edited, so wait for the
+            // next change rather than giving up.
+            let current = match Self::get_toml(None, None) {
+                Ok(project) => Self::get_watched_files(&project),
+                Err(_) => continue,
+            };
+
+            if current != snapshot {
+                snapshot = current;
+                pending = true;
+                last_change = Some(Instant::now());
+            }
+        }
+    }
+
+    pub fn coverage(args: &[String]) -> Result<(), StrRet> {
+        let mut build_args = vec!["--coverage".to_string()];
+        let mut program_args = vec![];
+        let mut bins = vec![];
+        let mut html = false;
+        let mut fail_under = None;
+        let mut gcov_command = String::from("gcov");
+
+        let mut index = 0;
+        while index < args.len() {
+            match args[index].as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean coverage [OPTIONS] [-- ARGS]
+
+Builds the current project with coverage instrumentation, runs it and reports line and branch coverage. The lcov \
+                         tracefile is written to coverage.info in the coverage build directory.
+
+Options:
+    --bin [all, <bin_name>]     Runs and reports on a single binary instead of the main project.
+    --html                      Also writes an HTML report to the html directory next to coverage.info.
+    --fail-under [PERCENT]      Fails if the total line coverage is below PERCENT.
+    --gcov [COMMAND]            Uses COMMAND to read coverage data (default is gcov, use \"llvm-cov gcov\" for Clang).
+    -v, --verbose               Makes the compiler output verbose.
+    -f, --flags                 Passes custom flags to the compiler.
+            "
+                    );
+                    return Ok(());
+                },
+                "--html" => html = true,
+                "--fail-under" => {
+                    index += 1;
+                    fail_under = match args.get(index).map(|v| v.trim_end_matches('%').parse::<f64>()) {
+                        Some(Ok(percent)) => Some(percent),
+                        _ => return Err("Did not provide a valid percentage to --fail-under".into()),
+                    };
+                },
+                "--gcov" => {
+                    index += 1;
+                    gcov_command = match args.get(index) {
+                        Some(command) => command.clone(),
+                        None => return Err("Did not provide a command to --gcov".into()),
+                    };
+                },
+                "--bin" => {
+                    index += 1;
+                    match args.get(index) {
+                        Some(name) => {
+                            bins.push(name.clone());
+                            build_args.push("--bin".to_string());
+                            build_args.push(name.clone());
+                        },
+                        None => return Err("Did not provide binary name or all as paramater to --bin".into()),
+                    }
+                },
+                "--" => {
+                    program_args = args[index + 1..].to_vec();
+                    break;
+                },
+                arg => build_args.push(arg.to_string()),
+            }
+
+            index += 1;
+        }
+
+        let project = Self::get_toml(None, None)?;
+        let root = current_dir().unwrap();
+        let build_path = format!("{}/coverage", project.get_directories().get_build_dir());
+        let object_path = format!("{}/coverage", project.get_directories().get_objects_dir());
+
+        // Counters from earlier runs would otherwise be added to this run's results.
+        for directory in &[&build_path, &object_path] {
+            for file in get_files_recursive(Path::new(directory)) {
+                if file.extension().and_then(OsStr::to_str) == Some("gcda") {
+                    remove_file(file).unwrap_or(());
+                }
+            }
+        }
+
+        Self::build(&build_args)?;
+
+        // Each target is the executable to run, and the notes file or object
+        // and source file of each of its translation units to pass to gcov.
+        let mut targets: Vec<(String, Vec<(String, PathBuf)>)> = vec![];
+
+        if !bins.is_empty() {
+            for binary in project.get_binaries() {
+                if bins[0] != "all" && !bins.contains(&binary.name) {
+                    continue;
+                }
+
+                let notes = format!(
+                    "{}/{}-{}.gcno",
+                    build_path,
+                    binary.name,
+                    binary.path.file_stem().unwrap().to_str().unwrap()
+                );
+
+                targets.push((binary.name.clone(), vec![(notes, binary.path.clone())]));
+            }
+        } else {
+            let units = Self::get_compilable_files(&project)?
+                .into_iter()
+                .map(|file| (Self::get_object_path(&project, &object_path, &file), file))
+                .collect();
+
+            targets.push((project.get_name().clone(), units));
+        }
+
+        let mut failures = vec![];
+        let mut report = Report::new();
+
+        for (name, units) in targets {
+            let executable_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+            let executable_path = format!("{}/{}", build_path, executable_name);
+
+            if !Path::new(&executable_path).exists() {
+                return Err(format!(
+                    "Cannot find the \"{}\" executable. Did it compile properly?",
+                    executable_name
+                )
+                .into());
+            }
+
+            println!("\n[Running '{}' with coverage]", executable_name);
+
+            let mut command = Command::new(format!("./{}", executable_path));
+            command.args(&program_args);
+
+            let status = command
+                .spawn()
+                .expect("Could not start application")
+                .wait()
+                .expect("Application exited unexpectedly");
+
+            if !status.success() {
+                failures.push(format!("'{}' exited with {}", executable_name, status));
+            }
+
+            // gcov finds the notes and counters of each translation unit next to
+            // the object it is given.
+            for (notes, source) in units {
+                let mut gcov_parts = gcov_command.split_whitespace();
+                let gcov = gcov_parts.next().unwrap_or("gcov");
+
+                let output = match Command::new(gcov)
+                    .args(gcov_parts)
+                    .args([
+                        "--branch-probabilities",
+                        "--branch-counts",
+                        "--stdout",
+                        "--object-directory",
+                    ])
+                    .arg(&notes)
+                    .arg(&source)
+                    .output()
+                {
+                    Ok(output) => output,
+                    Err(e) => return Err(format!("Could not execute \"{}\": {}", gcov_command, e).into()),
+                };
+
+                report.add_gcov_output(&String::from_utf8_lossy(&output.stdout), &root);
+            }
+        }
+
+        if report.is_empty() {
+            return Err("No coverage data was found. Did the program run?".into());
+        }
+
+        println!("\n{}", report.summary());
+
+        let tracefile_path = format!("{}/coverage.info", build_path);
+        let mut tracefile = File::create(&tracefile_path).expect("Could not create coverage.info");
+        tracefile
+            .write_all(report.to_lcov(&root).as_bytes())
+            .expect("Could not write to coverage.info");
+
+        println!("\nWrote lcov tracefile to {}", tracefile_path);
+
+        if html {
+            let html_path = format!("{}/html", build_path);
+            create_dir_all(&html_path).expect("Could not create HTML report directory");
+
+            for (page_name, page) in report.to_html(project.get_name()) {
+                let mut file = File::create(format!("{}/{}", html_path, page_name))
+                    .unwrap_or_else(|_| panic!("Could not create {}", page_name));
+                file.write_all(page.as_bytes())
+                    .unwrap_or_else(|_| panic!("Could not write to {}", page_name));
+            }
+
+            println!("Wrote HTML report to {}/index.html", html_path);
+        }
+
+        if !failures.is_empty() {
+            return Err(failures.join(", ").into());
+        }
+
+        if let Some(minimum) = fail_under {
+            if report.get_line_coverage() < minimum {
+                return Err(format!(
+                    "Line coverage of {:.2}% is below the minimum of {:.2}%",
+                    report.get_line_coverage(),
+                    minimum
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check(args: &[String]) -> Result<(), StrRet> {
+        let mut build_mode = "debug";
+        let mut compiler_flags = String::new();
+        let mut jobs = get_job_count();
+
+        for (index, arg) in args.iter().enumerate() {
+            match arg.as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean check [OPTIONS] [-f [FLAGS]]
+
+Checks every source file and binary of the current project for errors, without producing any object files or \
+                         executables. Files that have not changed since they were last checked, along with the \
+                         headers they include, are skipped.
+
+Options:
+    -d, --debug                 Checks with the debug mode flags (this is turned on by default).
+    -r, --release               Checks with the release mode flags.
+    -j, --jobs [JOBS]           Sets the number of files checked at once (default is the number of CPUs).
+    --features [FEATURES]       Enables the given features, split by commas.
+    --no-default-features       Does not enable the default features.
+    --all-features              Enables every feature.
+    -f, --flags                 Passes custom flags to the compiler.
+            "
+                    );
+                    return Ok(());
+                },
+                "-r" | "--release" => build_mode = "release",
+                "-d" | "--debug" => build_mode = "debug",
+                "-j" | "--jobs" =>
+                    jobs = match args.get(index + 1).map(|j| j.parse()) {
+                        Some(Ok(j)) => j,
+                        _ => return Err("Did not provide a valid number of jobs to --jobs".into()),
+                    },
+                "-f" | "--flags" => compiler_flags = args[index + 1..].to_vec().join(" "),
+                _ => (),
+            }
+        }
+
+        let mut project = Self::get_toml(None, None)?;
+        Self::select_features(&mut project, args)?;
+
+        let units = Self::get_translation_units(&project, build_mode, &compiler_flags)?
+            .into_iter()
+            .map(|(file, compiler, flags)| {
+                let mut compiler = vec![compiler];
+                compiler.extend(flags);
+
+                let mut command = compiler.clone();
+                command.push("-fsyntax-only".to_string());
+                command.push(file.to_str().unwrap().to_string());
+
+                (file, compiler, command)
+            })
+            .collect();
+
+        let cache_path = format!("{}/check.lock", project.get_directories().get_objects_dir());
+        let results = Self::run_cached(&cache_path, units, jobs, true, "Checking")?;
+
+        let mut failed = 0;
+        for result in results {
+            if !result.output.is_empty() {
+                eprint!("{}", result.output);
+            }
+
+            if !result.success {
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            return Err(format!("{} file(s) failed to check", failed).into());
+        }
+
+        println!("Finished checking without errors");
+
+        Ok(())
+    }
+
+    pub fn lint(args: &[String]) -> Result<(), StrRet> {
+        let mut build_mode = "debug";
+        let mut compiler_flags = String::new();
+        let mut jobs = get_job_count();
+        let mut fix = false;
+        let mut deny_warnings = None;
+
+        for (index, arg) in args.iter().enumerate() {
+            match arg.as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean lint [OPTIONS] [-f [FLAGS]]
+
+Runs clang-tidy over every source file and binary of the current project, using the same flags that the project is \
+                         built with. The checks that are run can be set with the \"checks\" key in the [lint] section \
+                         of Ocean.toml. Files that have not changed since they were last linted, along with the \
+                         headers they include, are skipped.
+
+Options:
+    -d, --debug                 Lints with the debug mode flags (this is turned on by default).
+    -r, --release               Lints with the release mode flags.
+    --fix                       Applies the fixes suggested by clang-tidy.
+    --deny-warnings             Fails if any warnings are found.
+    --allow-warnings            Does not fail if warnings are found, even if deny_warnings is set in Ocean.toml.
+    -j, --jobs [JOBS]           Sets the number of files linted at once (default is the number of CPUs).
+    --features [FEATURES]       Enables the given features, split by commas.
+    --no-default-features       Does not enable the default features.
+    --all-features              Enables every feature.
+    -f, --flags                 Passes custom flags to the compiler.
+            "
+                    );
+                    return Ok(());
+                },
+                "-r" | "--release" => build_mode = "release",
+                "-d" | "--debug" => build_mode = "debug",
+                "--fix" => fix = true,
+                "--deny-warnings" => deny_warnings = Some(true),
+                "--allow-warnings" => deny_warnings = Some(false),
+                "-j" | "--jobs" =>
+                    jobs = match args.get(index + 1).map(|j| j.parse()) {
+                        Some(Ok(j)) => j,
+                        _ => return Err("Did not provide a valid number of jobs to --jobs".into()),
+                    },
+                "-f" | "--flags" => compiler_flags = args[index + 1..].to_vec().join(" "),
+                _ => (),
+            }
+        }
+
+        let mut project = Self::get_toml(None, None)?;
+        Self::select_features(&mut project, args)?;
+
+        let options = project.get_lint_options();
+        let deny_warnings = deny_warnings.unwrap_or(options.deny_warnings);
+
+        let units = Self::get_translation_units(&project, build_mode, &compiler_flags)?
+            .into_iter()
+            .map(|(file, compiler, flags)| {
+                let mut command = vec![options.command.clone()];
+
+                if !options.checks.is_empty() {
+                    command.push(format!("--checks={}", options.checks.join(",")));
+                }
 
-            println!("Compiled {}.o", file.file_stem().unwrap().to_str().unwrap());
-        }
+                if fix {
+                    command.push("--fix".to_string());
+                }
 
-        for file in read_dir(&object_path)
-            .expect("Could not read objects directory")
-            .into_iter()
-        {
-            if let Ok(file) = file {
-                object_files.push(format!("{}/{}", object_path, file.file_name().to_str().unwrap()));
-            }
-        }
+                command.push(file.to_str().unwrap().to_string());
+                command.push("--".to_string());
+                command.extend(flags.iter().cloned());
 
-        let mut c = Command::new(compiler);
+                let mut compiler = vec![compiler];
+                compiler.extend(flags);
 
-        if is_verbose {
-            c.arg("-v");
-        }
+                (file, compiler, command)
+            })
+            .collect();
 
-        for obj in object_files {
-            c.arg(obj);
-        }
+        // Fixing changes the files that were linted, so the results no longer
+        // describe their contents.
+        let cache_path = format!("{}/lint.lock", project.get_directories().get_objects_dir());
+        let results = Self::run_cached(&cache_path, units, jobs, !fix, "Linting")?;
 
-        c.arg("-o").arg(format!("{}/{}", build_path, executable_name));
+        let mut failed = 0;
+        let mut diagnostics = BTreeMap::new();
 
-        for library_directory in project.get_library_dirs() {
-            c.arg(format!("-L{}", library_directory));
+        for result in results {
+            if !result.success {
+                failed += 1;
+            }
+
+            for (path, mut found) in parse_diagnostics(&result.output) {
+                diagnostics.entry(path).or_insert_with(Vec::new).append(&mut found);
+            }
         }
 
-        for library in project.get_libraries() {
-            c.arg(format!("-l{}", library));
+        let mut warnings = 0;
+        let mut errors = 0;
+
+        for (path, found) in diagnostics {
+            // Headers are reported by every file that includes them.
+            let mut seen = vec![];
+            let found: Vec<_> = found
+                .into_iter()
+                .filter(|d| {
+                    let key = (d.location.clone(), d.message.clone());
+                    let new = !seen.contains(&key);
+                    seen.push(key);
+                    new
+                })
+                .collect();
+
+            let file_warnings = found.iter().filter(|d| d.severity == "warning").count();
+            let file_errors = found.iter().filter(|d| d.severity == "error").count();
+            warnings += file_warnings;
+            errors += file_errors;
+
+            println!("\n{} ({} warning(s), {} error(s))", path, file_warnings, file_errors);
+
+            for diagnostic in found {
+                println!(
+                    "  {}: {}: {}",
+                    diagnostic.location, diagnostic.severity, diagnostic.message
+                );
+
+                for line in diagnostic.details {
+                    println!("    {}", line);
+                }
+            }
         }
 
-        if let Err(e) = c.spawn().expect("Could not find compiler executable").wait() {
-            return Err(format!("Compiler command returned with error code: {}", e).into());
-        };
+        println!("\nFound {} warning(s) and {} error(s)", warnings, errors);
 
-        let mut lock = File::create(lock_file).expect("Could not create/truncate Ocean.lock");
-        cache.update_cache(&project)?;
-        if !cache.get_files().is_empty() {
-            lock.write_all(
-                toml::to_string_pretty(&cache)
-                    .expect("Could not convert into Cache")
-                    .as_bytes(),
-            )
-            .expect("Could not write to Ocean.lock");
-        } else {
-            eprintln!("Could not find any files in source directory.");
-            remove_file(lock_file).expect("Could not remove lock file");
+        if failed > 0 {
+            return Err(format!("clang-tidy failed on {} file(s)", failed).into());
+        } else if errors > 0 {
+            return Err(format!("Found {} error(s)", errors).into());
+        }
+
+        if deny_warnings && warnings > 0 {
+            return Err(format!("Found {} warning(s) with warnings denied", warnings).into());
         }
 
         Ok(())
     }
 
-    pub fn run(args: &[String]) -> Result<(), StrRet> {
-        let mut build_mode = "debug";
-        let mut program_args = vec![];
-        let mut bins = vec![];
+    pub fn fmt(args: &[String]) -> Result<(), StrRet> {
+        let mut check = false;
+        let mut jobs = get_job_count();
 
         for (index, arg) in args.iter().enumerate() {
             match arg.as_str() {
                 "--help" => {
                     println!(
                         "
-Usage: ocean run [OPTIONS]
+Usage: ocean fmt [OPTIONS]
 
-By default, this run projects in debug mode.
+Formats every C and C++ source and header file in the source directory, the tests directory and the project's binaries \
+                         with clang-format. A .clang-format file in the project root is used if there is one, \
+                         otherwise the \"style\" key of the [format] section of Ocean.toml is used.
 
 Options:
-    -d, --debug                 Runs the current project in debug mode (this is turned on by default)
-    -r, --release               Runs the current project in release mode
-    --bin [all, <bin_name>]     Builds a single file as a single executable.
-    -v, --verbose               Makes the compiler output verbose.
-    -f, --flags                 Passes custom flags to the compiler.
+    --check                     Lists the files that are not formatted instead of formatting them, and fails if there \
+                         are any.
+    -j, --jobs [JOBS]           Sets the number of files formatted at once (default is the number of CPUs).
             "
                     );
                     return Ok(());
                 },
-                "-r" | "--release" => build_mode = "release",
-                "-d" | "--debug" => build_mode = "debug",
-                "--bin" => bins.push(
-                    if let Some(arg) = args.get(index as usize + 1) {
-                        arg
-                    } else {
-                        return Err("Did not provide binary name or all as paramater to --bin".into());
+                "--check" => check = true,
+                "-j" | "--jobs" =>
+                    jobs = match args.get(index + 1).map(|j| j.parse()) {
+                        Some(Ok(j)) => j,
+                        _ => return Err("Did not provide a valid number of jobs to --jobs".into()),
                     },
-                ),
-                "--" => program_args = args[index + 1..].to_vec(),
-                _ => (), // -v and -f are handled by the build function
+                _ => (),
             }
         }
 
-        Self::build(args)?;
-
         let project = Self::get_toml(None, None)?;
+        let options = project.get_format_options();
+        let extensions = [
+            "c", "h", "cc", "cpp", "cxx", "c++", "hh", "hpp", "hxx", "h++", "inl", "ipp",
+        ];
+
+        let mut files: Vec<PathBuf> = [project.get_directories().get_source_dir().as_str(), "./tests"]
+            .iter()
+            .flat_map(|dir| get_files_recursive(Path::new(dir)))
+            .filter(|file| extensions.contains(&file.extension().and_then(OsStr::to_str).unwrap_or_default()))
+            .collect();
+
+        files.extend(project.get_binaries().into_iter().map(|binary| binary.path));
+        files.sort();
+        files.dedup();
+
+        if files.is_empty() {
+            return Err("No source files found.".into());
+        }
 
-        let run = |name: String, program_args: &Vec<String>| -> Result<(), StrRet> {
-            let executable_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
-            let executable_path = format!(
-                "{}/{}/{}",
-                project.get_directories().get_build_dir(),
-                build_mode,
-                executable_name
-            );
+        let style = if Path::new(".clang-format").exists() || Path::new("_clang-format").exists() {
+            "--style=file".to_string()
+        } else {
+            format!("--style={}", options.style)
+        };
 
-            if Path::new(&executable_path).exists() {
-                println!("\n[Running '{}']", executable_name);
-                Command::new(format!("./{}", executable_path))
-                    .args(program_args)
-                    .spawn()
-                    .expect("Could not start application")
-                    .wait()
-                    .expect("Application exited unexpectedly");
+        let results = run_parallel(files, jobs, |file| {
+            let mut command = Command::new(&options.command);
+            command.arg(&style);
 
-                Ok(())
-            } else {
-                Err(format!(
-                    "Cannot find the \"{}\" executable. Did it compile properly?",
-                    executable_name
-                )
-                .into())
+            if !check {
+                command.arg("-i");
             }
-        };
 
-        if !bins.is_empty() {
-            let project_binaries = project.get_binaries();
+            let output = match command.arg(&file).output() {
+                Ok(output) if output.status.success() => output,
+                Ok(output) => return Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+                Err(e) => return Err(format!("Could not execute \"{}\": {}", options.command, e)),
+            };
 
-            if bins[0] == "all" {
-                for binary in project_binaries {
-                    run(binary.name.clone(), &program_args)?;
-                }
-            } else {
-                for bin_name in bins {
-                    for binary in project_binaries.iter().filter(|binary| binary.name == *bin_name) {
-                        run(binary.name.clone(), &program_args)?;
-                    }
-                }
-            }
+            let formatted = !check || read(&file).map(|contents| contents == output.stdout).unwrap_or(false);
 
-            return Ok(());
+            Ok((file, formatted))
+        });
+
+        let mut total = 0;
+        let mut unformatted = vec![];
+
+        for result in results {
+            let (file, formatted) = result?;
+            total += 1;
+
+            if !formatted {
+                unformatted.push(file);
+            }
         }
 
-        let executable_name = format!("{}{}", project.get_name(), env::consts::EXE_SUFFIX);
-        run(executable_name, &program_args)?;
+        if check {
+            for file in &unformatted {
+                println!("{}", file.display());
+            }
+
+            if !unformatted.is_empty() {
+                return Err(format!("{} of {} file(s) are not formatted", unformatted.len(), total).into());
+            }
+
+            println!("All {} file(s) are formatted", total);
+        } else {
+            println!("Formatted {} file(s)", total);
+        }
 
         Ok(())
     }
@@ -531,6 +2238,7 @@ Options:
 
         let mut do_ccls = false;
         let mut do_vscode = false;
+        let mut do_format = false;
 
         if !args.is_empty() {
             match args[0].as_str() {
@@ -547,6 +2255,7 @@ Options:
     -s, --source-dir    Sets the source directory (default is \"./src\").
     -o, --obj-dir       Sets the objects directory (default is \"./obj\").
     -c, --compiler      Sets the compiler for the current project (default is gcc for C and g++ for C++).
+    --format-style      Sets the clang-format style (e.g. LLVM, Google) and writes a matching .clang-format.
     --ccls              Outputs a .ccls file to be used with ccls. Allows a language server to be used with an editor \
                          like Vim, for example.
     --vscode            Outputs Visual Studio Code config files to make writing C/C++ easier.
@@ -572,7 +2281,7 @@ Options:
         }
 
         if Path::new(&format!("{}/", project.get_name())).exists()
-            && read_dir(&format!("{}/", project.get_name())).unwrap().next().is_none()
+            && read_dir(format!("{}/", project.get_name())).unwrap().next().is_none()
         {
             return Err("Cannot create a new project, directory is not empty".to_string());
         }
@@ -596,9 +2305,16 @@ Options:
                 "-c" | "--compiler" => project.get_compiler_mut().set_compiler_command(
                     lang,
                     args.get(index + 2)
-                        .unwrap_or_else(|| panic!(("Did not specify custom {} compiler", lang)))
+                        .unwrap_or_else(|| panic!("Did not specify custom {} compiler", lang))
                         .clone(),
                 ),
+                "--format-style" => {
+                    project.set_format_options(FormatOptions {
+                        style: args.get(index + 2).expect("Did not specify a format style").clone(),
+                        ..FormatOptions::default()
+                    });
+                    do_format = true;
+                },
                 "--ccls" => do_ccls = true,
                 "--vscode" => do_vscode = true,
                 _ => (),
@@ -623,15 +2339,14 @@ int main() {
 }
 ",
         };
-        let ignore_content = "/build/\n/obj/\nOcean.lock";
+        let ignore_content = "/build/\n/obj/";
 
-        create_dir_all(&format!("{}/src", project.get_name())).expect("Could not create project and source directory");
-        let mut file =
-            File::create(&format!("{}/Ocean.toml", project.get_name())).expect("Could not create Ocean.toml");
+        create_dir_all(format!("{}/src", project.get_name())).expect("Could not create project and source directory");
+        let mut file = File::create(format!("{}/Ocean.toml", project.get_name())).expect("Could not create Ocean.toml");
         file.write_all(toml_content.as_bytes())
             .expect("Could not write to Ocean.toml");
 
-        let mut code_file = File::create(&format!(
+        let mut code_file = File::create(format!(
             "{}/{}/main.{}",
             project.get_name(),
             project.get_directories().get_source_dir(),
@@ -644,15 +2359,23 @@ int main() {
             .unwrap_or_else(|_| panic!("Could not write to main.{}", project.get_language().get_extension()));
 
         let mut ignore_file =
-            File::create(&format!("{}/.gitignore", project.get_name())).expect("Could not create .gitignore");
+            File::create(format!("{}/.gitignore", project.get_name())).expect("Could not create .gitignore");
         ignore_file
             .write_all(ignore_content.as_bytes())
             .expect("Could not write into .gitignore");
 
+        if do_format {
+            let mut format_file =
+                File::create(format!("{}/.clang-format", project.get_name())).expect("Could not create .clang-format");
+            format_file
+                .write_all(project.get_format_options().get_clang_format_config().as_bytes())
+                .expect("Could not write to .clang-format");
+        }
+
         if do_ccls {
             // TODO
             let _ = CCLS::new();
-            let _ = File::create(&format!("{}/.ccls", project.get_name())).expect("Could not create .ccls");
+            let _ = File::create(format!("{}/.ccls", project.get_name())).expect("Could not create .ccls");
         }
 
         if do_vscode {
@@ -705,6 +2428,406 @@ int main() {
         Ok(())
     }
 
+    // The manifest is installed along with the project, so that cleaning the
+    // build directory does not lose track of what was installed.
+    fn get_install_manifest_path(project: &Project, prefix: &Path) -> PathBuf {
+        prefix
+            .join("share/ocean")
+            .join(project.get_name())
+            .join("install_manifest.txt")
+    }
+
+    // Where the manifest was written before it was installed with the project.
+    fn get_legacy_install_manifest_path(project: &Project) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/release/install_manifest.txt",
+            project.get_directories().get_build_dir()
+        ))
+    }
+
+    fn build_release(project: &Project) -> Result<(), StrRet> {
+        Self::build(&["--release".to_string()])?;
+
+        if !project.get_binaries().is_empty() {
+            Self::build(&["--release".to_string(), "--bin".to_string(), "all".to_string()])?;
+        }
+
+        Ok(())
+    }
+
+    // Returns the files that a release build installs below `prefix`. The
+    // directories are relative to the prefix unless they are absolute. The
+    // pkg-config file of a library is written to the build directory first,
+    // since its contents depend on where it is installed.
+    fn get_install_files(
+        project: &Project,
+        prefix: &Path,
+        bindir: &str,
+        libdir: &str,
+        includedir: &str,
+    ) -> Result<Vec<InstallFile>, StrRet> {
+        let bindir = prefix.join(bindir);
+        let libdir = prefix.join(libdir);
+        let includedir = prefix.join(includedir);
+
+        let build_path = PathBuf::from(format!("{}/release", project.get_directories().get_build_dir()));
+        let output_name = PathBuf::from(project.get_output_name());
+        let mut files = vec![];
+
+        files.push(match project.get_kind() {
+            ProjectKind::Executable => InstallFile {
+                source: build_path.join(&output_name),
+                directory: bindir.clone(),
+                name: output_name,
+                mode: 0o755,
+            },
+            ProjectKind::Static => InstallFile {
+                source: build_path.join(&output_name),
+                directory: libdir.clone(),
+                name: output_name,
+                mode: 0o644,
+            },
+            ProjectKind::Shared => InstallFile {
+                source: build_path.join(&output_name),
+                directory: libdir.clone(),
+                name: output_name,
+                mode: 0o755,
+            },
+        });
+
+        for binary in project.get_binaries() {
+            let name = PathBuf::from(format!("{}{}", binary.name, env::consts::EXE_SUFFIX));
+
+            files.push(InstallFile {
+                source: build_path.join(&name),
+                directory: bindir.clone(),
+                name,
+                mode: 0o755,
+            });
+        }
+
+        for directory in project.get_include_dirs() {
+            for header in get_files_recursive(Path::new(&directory)) {
+                files.push(InstallFile {
+                    name: header.strip_prefix(&directory).unwrap_or(&header).to_path_buf(),
+                    source: header,
+                    directory: includedir.clone(),
+                    mode: 0o644,
+                });
+            }
+        }
+
+        if project.get_kind() != ProjectKind::Executable {
+            let pc_name = PathBuf::from(format!("{}.pc", project.get_name()));
+            let pc_path = build_path.join(&pc_name);
+
+            if let Err(e) = File::create(&pc_path).and_then(|mut file| {
+                file.write_all(pkg_config::generate(project, prefix, &libdir, &includedir).as_bytes())
+            }) {
+                return Err(format!("Could not write {}: {}", pc_path.display(), e).into());
+            }
+
+            files.push(InstallFile {
+                source: pc_path,
+                directory: libdir.join("pkgconfig"),
+                name: pc_name,
+                mode: 0o644,
+            });
+        }
+
+        Ok(files)
+    }
+
+    pub fn install(args: &[String]) -> Result<(), StrRet> {
+        let mut prefix = String::from("/usr/local");
+        let mut destdir = env::var("DESTDIR").ok().filter(|destdir| !destdir.is_empty());
+        let mut bindir = String::from("bin");
+        let mut libdir = String::from("lib");
+        let mut includedir = String::from("include");
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = match arg.as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean install [OPTIONS]
+
+Builds the current project in release mode and installs its executables, libraries and public headers. A manifest of \
+                         the installed files is installed to <prefix>/share/ocean/<name>/ for ocean uninstall.
+
+Options:
+    --prefix DIR        Installs below DIR (defaults to /usr/local).
+    --destdir DIR       Stages the install below DIR, for packaging (defaults to $DESTDIR).
+    --bindir DIR        Installs executables to DIR, relative to the prefix (defaults to bin).
+    --libdir DIR        Installs libraries to DIR, relative to the prefix (defaults to lib).
+    --includedir DIR    Installs public headers to DIR, relative to the prefix (defaults to include).
+            "
+                    );
+                    return Ok(());
+                },
+                "--prefix" | "--destdir" | "--bindir" | "--libdir" | "--includedir" => match args.next() {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("No directory given to {}", arg).into()),
+                },
+                _ => return Err(format!("Unknown install option \"{}\"", arg).into()),
+            };
+
+            match arg.as_str() {
+                "--prefix" => prefix = value,
+                "--destdir" => destdir = Some(value),
+                "--bindir" => bindir = value,
+                "--libdir" => libdir = value,
+                _ => includedir = value,
+            }
+        }
+
+        let project = Self::get_toml(None, None)?;
+
+        Self::build_release(&project)?;
+
+        let prefix = get_absolute_path(&prefix);
+        let legacy_manifest_path = Self::get_legacy_install_manifest_path(&project);
+
+        let mut installer = Installer::new(prefix.clone(), destdir.map(|destdir| get_absolute_path(&destdir)));
+        let manifest_path = Self::get_install_manifest_path(&project, &prefix);
+        installer.keep_previous(&legacy_manifest_path);
+        installer.keep_previous(&installer.get_staged_path(&manifest_path));
+
+        for file in Self::get_install_files(&project, &prefix, &bindir, &libdir, &includedir)? {
+            installer.install_file(&file)?;
+        }
+
+        installer.write_manifest(&manifest_path)?;
+        remove_file(&legacy_manifest_path).unwrap_or(());
+
+        println!(
+            "Installed {} to {}",
+            project.get_name(),
+            installer.get_staged_path(&prefix).display()
+        );
+
+        Ok(())
+    }
+
+    pub fn uninstall(args: &[String]) -> Result<(), StrRet> {
+        let mut prefix = String::from("/usr/local");
+        let mut destdir = env::var("DESTDIR").ok().filter(|destdir| !destdir.is_empty());
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = match arg.as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean uninstall [OPTIONS]
+
+Removes the files and directories that the last ocean install created, as recorded in the manifest it installed.
+
+Options:
+    --prefix DIR        Uninstalls from below DIR (defaults to /usr/local).
+    --destdir DIR       Uninstalls from a staged install below DIR (defaults to $DESTDIR).
+            "
+                    );
+                    return Ok(());
+                },
+                "--prefix" | "--destdir" => match args.next() {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("No directory given to {}", arg).into()),
+                },
+                _ => return Err(format!("Unknown uninstall option \"{}\"", arg).into()),
+            };
+
+            match arg.as_str() {
+                "--prefix" => prefix = value,
+                _ => destdir = Some(value),
+            }
+        }
+
+        let project = Self::get_toml(None, None)?;
+        let prefix = get_absolute_path(&prefix);
+        let installer = Installer::new(prefix.clone(), destdir.map(|destdir| get_absolute_path(&destdir)));
+        let mut manifest_path = installer.get_staged_path(&Self::get_install_manifest_path(&project, &prefix));
+
+        if !manifest_path.exists() && Self::get_legacy_install_manifest_path(&project).exists() {
+            manifest_path = Self::get_legacy_install_manifest_path(&project);
+        }
+
+        let removed = installer.uninstall(&manifest_path)?;
+        remove_file(&manifest_path).unwrap_or(());
+
+        println!("Removed {} installed file(s)", removed);
+
+        Ok(())
+    }
+
+    // Returns the files that go into a source package: the manifest, the build
+    // script, the sources, tests, public headers, binaries, resources and
+    // assets, and any README or licence in the project root. Nothing from the
+    // build or object directories is included, and neither is Ocean.lock.
+    fn get_source_package_files(project: &Project) -> Vec<PathBuf> {
+        let directories = project.get_directories();
+        let mut paths = vec![
+            PathBuf::from("Ocean.toml"),
+            PathBuf::from(BUILD_SCRIPT_PATH),
+            PathBuf::from(directories.get_source_dir()),
+            PathBuf::from("tests"),
+        ];
+
+        paths.extend(project.get_include_dirs().iter().map(PathBuf::from));
+        paths.extend(project.get_binaries().into_iter().map(|binary| binary.path));
+        paths.extend(project.get_asset_options().paths.iter().map(PathBuf::from));
+
+        for pattern in project.get_resources().values() {
+            if let Ok(matches) = glob::glob(pattern) {
+                paths.extend(matches.flatten());
+            }
+        }
+
+        if let Ok(entries) = read_dir(".") {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_uppercase();
+
+                if ["README", "LICENSE", "LICENCE", "COPYING"].iter().any(|prefix| name.starts_with(prefix)) {
+                    paths.push(PathBuf::from(entry.file_name()));
+                }
+            }
+        }
+
+        let normalise = |path: &Path| -> PathBuf {
+            path.components()
+                .filter(|component| *component != Component::CurDir)
+                .collect()
+        };
+        let excluded = [
+            normalise(Path::new(directories.get_build_dir())),
+            normalise(Path::new(directories.get_objects_dir())),
+        ];
+
+        let mut files: Vec<PathBuf> = paths
+            .iter()
+            .flat_map(|path| get_files_recursive(path))
+            .map(|file| normalise(&file))
+            .filter(|file| file.components().all(|component| matches!(component, Component::Normal(_))))
+            .filter(|file| !excluded.iter().any(|excluded| file.starts_with(excluded)))
+            .collect();
+
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    fn write_package(path: &Path, contents: &[u8]) -> Result<(), StrRet> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).unwrap_or(());
+        }
+
+        match File::create(path).and_then(|mut file| file.write_all(contents)) {
+            Ok(_) => {
+                println!("Packaged {}", path.display());
+                Ok(())
+            },
+            Err(e) => Err(format!("Could not write {}: {}", path.display(), e).into()),
+        }
+    }
+
+    pub fn package(args: &[String]) -> Result<(), StrRet> {
+        let mut binary = false;
+        let mut deb = false;
+        let mut prefix = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" => {
+                    println!(
+                        "
+Usage: ocean package [OPTIONS]
+
+Packages the current project's sources into build/package/<name>-<version>.tar.gz. The archives are reproducible: \
+                         entries are sorted and have fixed owners and timestamps (SOURCE_DATE_EPOCH, or 0 if it is \
+                         not set).
+
+Options:
+    --binary            Also builds the project in release mode and packages the files that ocean install would \
+                         install into <name>-<version>-<arch>-<os>.tar.gz.
+    --deb               Also builds the project in release mode and packages the files that ocean install would \
+                         install into a Debian package, <name>_<version>_<arch>.deb. The maintainer and dependencies \
+                         are read from the [deb] section of Ocean.toml.
+    --prefix DIR        Sets the prefix that binary packages install to (defaults to /usr/local, or /usr for --deb).
+            "
+                    );
+                    return Ok(());
+                },
+                "--binary" => binary = true,
+                "--deb" => deb = true,
+                "--prefix" =>
+                    prefix = match args.next() {
+                        Some(value) => Some(value.clone()),
+                        None => return Err("No directory given to --prefix".into()),
+                    },
+                _ => return Err(format!("Unknown package option \"{}\"", arg).into()),
+            }
+        }
+
+        let project = Self::get_toml(None, None)?;
+        let version = project.get_version().map(String::as_str).unwrap_or("0.0.0");
+        let package_path = PathBuf::from(format!("{}/package", project.get_directories().get_build_dir()));
+
+        let name = format!("{}-{}", project.get_name(), version);
+        let mut sources = Tarball::default();
+
+        for file in Self::get_source_package_files(&project) {
+            sources.add_file(Path::new(&name).join(&file), &file, get_archive_mode(&file))?;
+        }
+
+        Self::write_package(&package_path.join(format!("{}.tar.gz", name)), &sources.to_tar_gz()?)?;
+
+        if binary || deb {
+            Self::build_release(&project)?;
+        }
+
+        if binary {
+            let name = format!("{}-{}-{}", name, env::consts::ARCH, env::consts::OS);
+            let prefix = PathBuf::from(prefix.as_deref().unwrap_or("/usr/local"));
+            let mut binaries = Tarball::default();
+
+            for file in Self::get_install_files(&project, &prefix, "bin", "lib", "include")? {
+                let directory = file.directory.strip_prefix(&prefix).unwrap_or(&file.directory);
+                binaries.add_file(Path::new(&name).join(directory).join(&file.name), &file.source, file.mode)?;
+            }
+
+            Self::write_package(&package_path.join(format!("{}.tar.gz", name)), &binaries.to_tar_gz()?)?;
+        }
+
+        if deb {
+            let prefix = PathBuf::from(prefix.as_deref().unwrap_or("/usr"));
+            let mut data = Tarball::default();
+
+            for file in Self::get_install_files(&project, &prefix, "bin", "lib", "include")? {
+                let path: PathBuf = file
+                    .directory
+                    .join(&file.name)
+                    .components()
+                    .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)))
+                    .collect();
+
+                data.add_file(path, &file.source, file.mode)?;
+            }
+
+            let name = format!(
+                "{}_{}_{}.deb",
+                deb::get_package_name(&project)?,
+                deb::get_version(&project),
+                deb::get_architecture()?
+            );
+
+            Self::write_package(&package_path.join(name), &deb::build(&project, &data)?)?;
+        }
+
+        Ok(())
+    }
+
     pub fn clean() -> Result<(), String> {
         let project = Self::get_toml(None, None)?;
 
@@ -713,7 +2836,6 @@ int main() {
                 continue;
             }
 
-            remove_file("Ocean.lock").unwrap_or(());
             remove_dir_all(directory).unwrap_or(());
         }
 
@@ -722,7 +2844,6 @@ int main() {
 
     pub fn set_data(args: &[String]) -> Result<(), String> {
         let mut project = Self::get_toml(None, None)?;
-        let mut do_clean = true;
 
         let help = "
 Usage: ocean set [KEY]
@@ -731,19 +2852,26 @@ This sets values inside the Ocean project file to a value specified by the user.
                     it allows the user to set specific keys for a specified platform.
 
 Option:
+    authors [AUTHORS]                                   Sets the authors of the project, split by commas.
     build_dir [DIRECTORY]                               Sets the build directory for the project.
     c++_compiler [COMPILER], cxx_compiler [COMPILER]    Set the compiler being used for the C++ project.
     c_compiler [COMPILER]                               Sets the compiler being used for the C project.
     compiler [COMPILER], current_compiler [COMPILER]    Sets the current compiler being used for the project.
+    description [DESCRIPTION]                           Sets the description of the project.
     flags [FLAGS]                                       Sets the flags of the current compiler, split by commas.
+    homepage [URL]                                      Sets the homepage of the project.
+    kind [KIND]                                         Sets what the project builds: executable, static or shared.
     lang [LANG], language [LANG]                        Set the current language of the project.
     lib_dirs [DIRS], library_directories [DIRS]         Sets the library directories that would be searched by the \
                     linker, split by commas.
     libs [LIBS], libraries [LIBS]                       Sets the libraries being compiled with the project, split by \
                     commas.
+    license [LICENSE]                                   Sets the licence of the project, as an SPDX expression.
     name [NAME]                                         Sets the name of the project.
     object_dir [DIRECTORY]                              Sets the object output directory.
+    repository [URL]                                    Sets the source repository of the project.
     source_dir [DIRECTORY]                              Sets the source code directory.
+    version [VERSION]                                   Sets the version of the project, as a semantic version.
     ";
 
         if args.is_empty() {
@@ -758,9 +2886,19 @@ Option:
 
         if !args[1..].is_empty() {
             match (args[0].as_str(), &args[1]) {
-                ("name", n) => {
-                    project.set_name(n.clone());
-                    do_clean = false;
+                ("name", n) => project.set_name(n.clone()),
+                ("version", version) => project.set_version(version.clone())?,
+                ("authors", authors) =>
+                    project.set_authors(authors.split(',').map(|author| author.trim().to_string()).collect()),
+                ("description", description) => project.set_description(description.clone()),
+                ("license", license) => project.set_license(license.clone())?,
+                ("homepage", homepage) => project.set_homepage(homepage.clone()),
+                ("repository", repository) => project.set_repository(repository.clone()),
+                ("kind", kind) => match kind.to_lowercase().as_str() {
+                    "executable" => project.set_kind(ProjectKind::Executable),
+                    "static" => project.set_kind(ProjectKind::Static),
+                    "shared" => project.set_kind(ProjectKind::Shared),
+                    _ => return Err("Invalid project kind.".to_string()),
                 },
                 (c, lang) if c == "lang" || c == "language" => match lang.to_lowercase().as_str() {
                     "c++" | "cxx" => project.set_language(Language::CXX),
@@ -800,10 +2938,6 @@ Option:
             return Err("No data provided to set key with".to_string());
         }
 
-        if do_clean {
-            Self::clean()?
-        }
-
         let mut file = File::create("./Ocean.toml").expect("Couldn't open Ocean.toml");
         let toml_content = toml::to_string_pretty(&project).expect("Could not transform project data into Ocean.toml");
         let toml_content = Self::pretty_toml(toml_content);
@@ -823,18 +2957,25 @@ This gets the current values inside the Ocean project file related to a data key
                     platform name, it will get specific keys for a specified platform.
 
 Option:
+    authors                         Prints the authors of the project.
     bins, binaries                  Prints the names of the individual binaries for the current project.
     build_dir                       Prints the build directory for the current project.
     c++_compiler, cxx_compiler      Prints the compiler being used for the C++ project.
     c_compiler                      Prints the compiler being used for the C project.
     compiler, current_compiler      Prints the current compiler being used for the project.
+    description                     Prints the description of the project.
     flags                           Prints the flags of the current compiler.
+    homepage                        Prints the homepage of the project.
+    kind                            Prints what the project builds: executable, static or shared.
     lang, language                  Prints the current language of the project.
     lib_dirs, library_directories   Prints the library directories that would be searched by the linker.
     libs, libraries                 Prints the libraries being compiled with the project.  
+    license                         Prints the licence of the project.
     name                            Prints the name of the project.
     object_dir                      Prints the object output directory.
+    repository                      Prints the source repository of the project.
     source_dir                      Prints the source code directory.
+    version                         Prints the version of the project.
     ";
 
         if args.is_empty() {
@@ -845,7 +2986,8 @@ Option:
         match data {
             "--help" => println!("{}", help),
             "name" => println!("{}", project.get_name().clone()),
-            "lang" | "language" => println!("{}", project.get_language().to_string()),
+            "kind" => println!("{}", project.get_kind()),
+            "lang" | "language" => println!("{}", project.get_language()),
             "libs" | "libraries" => println!("{:#?}", project.get_libraries()),
             "lib_dirs" | "library_directories" => println!("{:#?}", project.get_directories().get_all_dirs()),
             "compiler" | "current_compiler" => println!(
@@ -868,6 +3010,12 @@ Option:
                 project.get_compiler().get_compiler_flags(project.get_language())
             ),
             "bins" | "binaries" => println!("{:#?}", project.get_binaries()),
+            "version" => println!("{}", project.get_version().map(String::as_str).unwrap_or_default()),
+            "authors" => println!("{:#?}", project.get_authors()),
+            "description" => println!("{}", project.get_description().map(String::as_str).unwrap_or_default()),
+            "license" => println!("{}", project.get_license().map(String::as_str).unwrap_or_default()),
+            "homepage" => println!("{}", project.get_homepage().map(String::as_str).unwrap_or_default()),
+            "repository" => println!("{}", project.get_repository().map(String::as_str).unwrap_or_default()),
             _ => eprintln!("Cannot find data key. Use --help to get help for this command."),
         };
         Ok(())
@@ -956,8 +3104,6 @@ Option:
             platform
         });
 
-        Self::clean()?;
-
         let mut file = File::create("./Ocean.toml").expect("Couldn't open Ocean.toml");
         let toml_content = toml::to_string_pretty(&project).expect("Could not transform project data into Ocean.toml");
         let toml_content = Self::pretty_toml(toml_content);
@@ -1035,3 +3181,65 @@ Option:
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_stay_in_the_object_directory() {
+        let project: Project = toml::from_str(
+            "[project]
+name = 'demo'
+language = 'C'
+libraries = []
+library_directories = []
+
+[directories]
+build_dir = './build'
+source_dir = './src'
+object_dir = './obj'
+
+[compiler.c]
+command = 'gcc'
+flags = []
+
+[compiler.cxx]
+command = 'g++'
+flags = []
+",
+        )
+        .unwrap();
+        let get_object_path = |file: &str| Commands::get_object_path(&project, "obj/debug", Path::new(file));
+        let absolute = |path: &Path| -> String {
+            path.components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name.to_str().unwrap()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+        let parent = current_dir().unwrap().parent().unwrap().to_path_buf();
+
+        assert_eq!(get_object_path("./src/main.c"), "obj/debug/main.o");
+        assert_eq!(get_object_path("src/net/tls.c"), "obj/debug/by-path/src/net/tls.o");
+        assert_eq!(get_object_path("./extra/strings.c"), "obj/debug/by-path/extra/strings.o");
+        assert_eq!(
+            get_object_path("../shared/x.c"),
+            format!("obj/debug/by-absolute-path/{}/shared/x.o", absolute(&parent))
+        );
+        assert_eq!(
+            get_object_path("src/../../shared/x.c"),
+            format!("obj/debug/by-absolute-path/{}/shared/x.o", absolute(&parent))
+        );
+        assert_eq!(get_object_path("/opt/lib/y.c"), "obj/debug/by-absolute-path/opt/lib/y.o");
+
+        for file in ["../../../../../../../../x.c", "/x.c", "../shared/../x.c"] {
+            let object = get_object_path(file);
+
+            assert!(Path::new(&object).starts_with("obj/debug/by-absolute-path"), "{}", object);
+            assert!(!Path::new(&object).components().any(|component| component == Component::ParentDir));
+        }
+    }
+}
@@ -1,3 +1,64 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    env::current_dir,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
 
 pub type StrRet = Cow<'static, str>;
+
+// Turns a path relative to the project root (such as "./build") into an
+// absolute one.
+pub fn get_absolute_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    current_dir().unwrap().join(path.strip_prefix(".").unwrap_or(path))
+}
+
+// Returns every file below `path`, sorted, or nothing if `path` does not exist.
+pub fn get_files_recursive(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+
+    if path.is_file() {
+        files.push(path.to_path_buf());
+    } else if let Ok(entries) = read_dir(path) {
+        for entry in entries.flatten() {
+            files.append(&mut get_files_recursive(&entry.path()));
+        }
+    }
+
+    files.sort();
+    files
+}
+
+pub fn get_job_count() -> usize { thread::available_parallelism().map(|n| n.get()).unwrap_or(1) }
+
+// Runs `job` over every item on up to `jobs` threads, returning the results in
+// the same order as the items.
+pub fn run_parallel<T: Send, R: Send>(items: Vec<T>, jobs: usize, job: impl Fn(T) -> R + Sync) -> Vec<R> {
+    let jobs = jobs.max(1).min(items.len().max(1));
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+
+                match next {
+                    Some((index, item)) => {
+                        let result = job(item);
+                        results.lock().unwrap().push((index, result));
+                    },
+                    None => break,
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
@@ -1,5 +1,6 @@
 use crate::language::*;
 use serde_derive::*;
+use std::{env, process::Command};
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct CompilerOptions {
@@ -9,6 +10,8 @@ pub struct CompilerOptions {
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Compiler {
+    // The command that static libraries are archived with, if not ar.
+    archiver: Option<String>,
     c: CompilerOptions,
     cxx: CompilerOptions,
 }
@@ -16,6 +19,7 @@ pub struct Compiler {
 impl Compiler {
     pub fn new() -> Self {
         Self {
+            archiver: None,
             cxx: CompilerOptions {
                 command: String::from("g++"),
                 flags: vec![],
@@ -48,6 +52,16 @@ impl Compiler {
             Language::CXX => &self.cxx.flags,
         }
     }
+
+    // $AR takes precedence over the archiver set in Ocean.toml.
+    pub fn get_archiver(&self) -> String {
+        env::var("AR")
+            .ok()
+            .filter(|archiver| !archiver.is_empty())
+            .or_else(|| self.archiver.clone())
+            .unwrap_or_else(|| String::from("ar"))
+    }
+
     pub fn set_compiler_flags(&mut self, lang: Language, flags: Vec<String>) {
         match lang {
             Language::C => self.c.flags = flags,
@@ -63,8 +77,49 @@ impl Default for Compiler {
 impl From<[CompilerOptions; 2]> for Compiler {
     fn from(co: [CompilerOptions; 2]) -> Self {
         Self {
+            archiver: None,
             cxx: co[0].clone(),
             c: co[1].clone(),
         }
     }
 }
+
+// The family ("gcc", "clang" or "unknown") and version of a compiler command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompilerIdentity {
+    pub family: String,
+    pub version: String,
+}
+
+impl CompilerIdentity {
+    fn run(command: &str, arg: &str) -> String {
+        match Command::new(command).arg(arg).output() {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // Asks the compiler what it is. Newer versions of GCC only print the major
+    // version for -dumpversion, so it is asked for -dumpfullversion instead.
+    pub fn detect(command: &str) -> Self {
+        let banner = Self::run(command, "--version").to_lowercase();
+
+        let family = if banner.contains("clang") {
+            "clang"
+        } else if banner.contains("gcc") || banner.contains("g++") || banner.contains("free software foundation") {
+            "gcc"
+        } else {
+            "unknown"
+        };
+
+        let version = match family {
+            "gcc" => Self::run(command, "-dumpfullversion"),
+            _ => Self::run(command, "-dumpversion"),
+        };
+
+        Self {
+            family: family.to_string(),
+            version,
+        }
+    }
+}
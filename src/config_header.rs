@@ -0,0 +1,66 @@
+use crate::{compiler::CompilerIdentity, project::Project};
+use std::{env, fmt::Write, process::Command};
+
+pub const CONFIG_HEADER: &str = "ocean_config.h";
+
+pub fn get_directory(project: &Project, build_mode: &str) -> String {
+    format!("{}/{}/config", project.get_directories().get_objects_dir(), build_mode)
+}
+
+pub fn get_header_path(project: &Project, build_mode: &str) -> String {
+    format!("{}/{}", get_directory(project, build_mode), CONFIG_HEADER)
+}
+
+fn quote(value: &str) -> String { format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")) }
+
+fn get_git_commit() -> Option<String> {
+    match Command::new("git").args(["rev-parse", "--short", "HEAD"]).output() {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        _ => None,
+    }
+}
+
+// Returns the contents of ocean_config.h. Nothing in it depends on when it was
+// generated, so writing it only when the contents differ keeps it from
+// triggering rebuilds.
+pub fn generate(project: &Project, build_mode: &str, compiler: &CompilerIdentity) -> String {
+    let options = project.get_config_header_options().unwrap_or_default();
+    let version = project.get_version().map(String::as_str).unwrap_or("0.0.0");
+    let mut numbers = version
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|number| number.parse::<u64>().unwrap_or(0));
+
+    let mut out = String::new();
+
+    writeln!(out, "/* Generated by Ocean. Do not edit. */").unwrap();
+    writeln!(out, "#ifndef OCEAN_CONFIG_H").unwrap();
+    writeln!(out, "#define OCEAN_CONFIG_H").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#define OCEAN_PROJECT_NAME {}", quote(project.get_name())).unwrap();
+    writeln!(out, "#define OCEAN_PROJECT_VERSION {}", quote(version)).unwrap();
+
+    for part in ["MAJOR", "MINOR", "PATCH"] {
+        writeln!(out, "#define OCEAN_PROJECT_VERSION_{} {}", part, numbers.next().unwrap_or(0)).unwrap();
+    }
+
+    writeln!(out, "#define OCEAN_PROFILE {}", quote(build_mode)).unwrap();
+    writeln!(out, "#define OCEAN_PROFILE_{} 1", build_mode.to_uppercase()).unwrap();
+    writeln!(out, "#define OCEAN_TARGET_OS {}", quote(env::consts::OS)).unwrap();
+    writeln!(out, "#define OCEAN_TARGET_ARCH {}", quote(env::consts::ARCH)).unwrap();
+    writeln!(out, "#define OCEAN_COMPILER {}", quote(&compiler.family)).unwrap();
+    writeln!(out, "#define OCEAN_COMPILER_VERSION {}", quote(&compiler.version)).unwrap();
+
+    if options.git_commit {
+        if let Some(commit) = get_git_commit() {
+            writeln!(out, "#define OCEAN_GIT_COMMIT {}", quote(&commit)).unwrap();
+        }
+    }
+
+    writeln!(out).unwrap();
+    writeln!(out, "#endif /* OCEAN_CONFIG_H */").unwrap();
+
+    out
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+#[derive(Default)]
+pub struct FileCoverage {
+    pub lines: BTreeMap<u32, u64>,
+    pub branches: BTreeMap<u32, Vec<Option<u64>>>,
+    pub functions: BTreeMap<String, (u32, u64)>,
+}
+
+impl FileCoverage {
+    pub fn get_line_totals(&self) -> (usize, usize) {
+        (self.lines.len(), self.lines.values().filter(|c| **c > 0).count())
+    }
+
+    pub fn get_branch_totals(&self) -> (usize, usize) {
+        let found = self.branches.values().map(|b| b.len()).sum();
+        let hit = self
+            .branches
+            .values()
+            .flatten()
+            .filter(|b| matches!(b, Some(c) if *c > 0))
+            .count();
+
+        (found, hit)
+    }
+
+    pub fn get_function_totals(&self) -> (usize, usize) {
+        (
+            self.functions.len(),
+            self.functions.values().filter(|(_, c)| *c > 0).count(),
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct Report {
+    files: BTreeMap<PathBuf, FileCoverage>,
+}
+
+fn percentage(found: usize, hit: usize) -> f64 {
+    if found == 0 {
+        100.0
+    } else {
+        hit as f64 * 100.0 / found as f64
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Adds the times a branch was taken to the branches of a line. Branches that
+// were never reached are None, unless another listing reached them.
+fn add_branch(branches: &mut Vec<Option<u64>>, index: usize, taken: Option<u64>) {
+    if branches.len() <= index {
+        branches.resize(index + 1, None);
+    }
+
+    branches[index] = match (branches[index], taken) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    };
+}
+
+impl Report {
+    pub fn new() -> Self { Self::default() }
+
+    // Parses the output of `gcov --branch-probabilities --branch-counts --stdout`.
+    // Files that are not inside `root` (system headers, for example) are
+    // skipped, and files that appear in more than one translation unit have their
+    // counts merged.
+    //
+    // Templates are listed once with the counts of every instantiation added
+    // together, and then once more for each instantiation, between lines of
+    // dashes. Line counts are only taken from the first listing, and branches,
+    // which gcov only shows per instantiation, are merged by line and index.
+    pub fn add_gcov_output(&mut self, output: &str, root: &Path) {
+        let mut current: Option<(PathBuf, FileCoverage)> = None;
+        let mut current_line = 0;
+        let mut pending_functions: Vec<(String, u64)> = vec![];
+        let mut in_instantiation = false;
+
+        for line in output.lines() {
+            if !line.is_empty() && line.chars().all(|c| c == '-') {
+                in_instantiation = false;
+                continue;
+            }
+
+            // Each instantiation starts with its symbol name, followed by a colon.
+            if line.ends_with(':') && !line.starts_with(char::is_whitespace) {
+                in_instantiation = true;
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("function ") {
+                let mut parts = rest.split_whitespace();
+                let name = parts.next().unwrap_or_default().to_string();
+                let count = match (parts.next(), parts.next()) {
+                    (Some("called"), Some(count)) => count.parse().unwrap_or(0),
+                    _ => 0,
+                };
+
+                pending_functions.push((name, count));
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("branch ") {
+                if let Some((_, file)) = current.as_mut() {
+                    let mut parts = rest.split_whitespace();
+                    let index = parts.next().and_then(|index| index.parse().ok()).unwrap_or(0);
+                    let taken = match (parts.next(), parts.next()) {
+                        (Some("taken"), Some(count)) => Some(count.trim_end_matches('%').parse().unwrap_or(0)),
+                        _ => None,
+                    };
+
+                    add_branch(file.branches.entry(current_line).or_default(), index, taken);
+                }
+
+                continue;
+            }
+
+            let mut fields = line.splitn(3, ':');
+            let (count, line_number, source) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(count), Some(line_number), Some(source)) => (count.trim(), line_number.trim(), source),
+                _ => continue,
+            };
+
+            let line_number: u32 = match line_number.parse() {
+                Ok(n) => n,
+                Err(_) => continue,
+            };
+
+            if line_number == 0 {
+                if let Some(path) = source.strip_prefix("Source:") {
+                    if let Some((path, file)) = current.take() {
+                        self.merge_file(path, file);
+                    }
+
+                    let path = PathBuf::from(path);
+                    if path.is_relative() || path.starts_with(root) {
+                        let path = path.strip_prefix(root).map(Path::to_path_buf).unwrap_or(path);
+                        current = Some((path, FileCoverage::default()));
+                    }
+                }
+
+                continue;
+            }
+
+            current_line = line_number;
+
+            let file = match current.as_mut() {
+                Some((_, file)) => file,
+                None => {
+                    pending_functions.clear();
+                    continue;
+                },
+            };
+
+            for (name, count) in pending_functions.drain(..) {
+                file.functions.entry(name).or_insert((line_number, 0)).1 += count;
+            }
+
+            let count = match count.trim_end_matches('*') {
+                "-" => continue,
+                _ if in_instantiation => continue,
+                "#####" | "=====" => 0,
+                c => c.parse().unwrap_or(0),
+            };
+
+            file.lines.entry(line_number).or_insert(count);
+        }
+
+        if let Some((path, file)) = current {
+            self.merge_file(path, file);
+        }
+    }
+
+    // A header is reported once per translation unit that includes it, so counts
+    // for the same line are summed rather than appended.
+    fn merge_file(&mut self, path: PathBuf, other: FileCoverage) {
+        let file = self.files.entry(path).or_default();
+
+        for (line, count) in other.lines {
+            *file.lines.entry(line).or_insert(0) += count;
+        }
+
+        for (line, branches) in other.branches {
+            let existing = file.branches.entry(line).or_default();
+
+            for (index, taken) in branches.into_iter().enumerate() {
+                add_branch(existing, index, taken);
+            }
+        }
+
+        for (name, (line, count)) in other.functions {
+            file.functions.entry(name).or_insert((line, 0)).1 += count;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.files.is_empty() }
+
+    pub fn get_line_coverage(&self) -> f64 {
+        let (found, hit) = self
+            .files
+            .values()
+            .map(FileCoverage::get_line_totals)
+            .fold((0, 0), |acc, x| (acc.0 + x.0, acc.1 + x.1));
+
+        percentage(found, hit)
+    }
+
+    pub fn get_branch_coverage(&self) -> f64 {
+        let (found, hit) = self
+            .files
+            .values()
+            .map(FileCoverage::get_branch_totals)
+            .fold((0, 0), |acc, x| (acc.0 + x.0, acc.1 + x.1));
+
+        percentage(found, hit)
+    }
+
+    pub fn summary(&self) -> String {
+        let width = self
+            .files
+            .keys()
+            .map(|p| p.display().to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max("Total".len());
+
+        let cell = |found: usize, hit: usize| format!("{:>7.2}% ({}/{})", percentage(found, hit), hit, found);
+        let mut out = format!("{:<width$}  {:<20}  {}\n", "File", "Lines", "Branches", width = width);
+
+        for (path, file) in &self.files {
+            let (lines_found, lines_hit) = file.get_line_totals();
+            let (branches_found, branches_hit) = file.get_branch_totals();
+
+            writeln!(
+                out,
+                "{:<width$}  {:<20}  {}",
+                path.display(),
+                cell(lines_found, lines_hit),
+                cell(branches_found, branches_hit),
+                width = width
+            )
+            .unwrap();
+        }
+
+        write!(
+            out,
+            "{:<width$}  {:<20}  {:>7.2}%",
+            "Total",
+            format!("{:>7.2}%", self.get_line_coverage()),
+            self.get_branch_coverage(),
+            width = width
+        )
+        .unwrap();
+
+        out
+    }
+
+    pub fn to_lcov(&self, root: &Path) -> String {
+        let mut out = String::new();
+
+        for (path, file) in &self.files {
+            writeln!(out, "TN:").unwrap();
+            writeln!(out, "SF:{}", root.join(path).display()).unwrap();
+
+            for (name, (line, _)) in &file.functions {
+                writeln!(out, "FN:{},{}", line, name).unwrap();
+            }
+
+            for (name, (_, count)) in &file.functions {
+                writeln!(out, "FNDA:{},{}", count, name).unwrap();
+            }
+
+            let (functions_found, functions_hit) = file.get_function_totals();
+            writeln!(out, "FNF:{}", functions_found).unwrap();
+            writeln!(out, "FNH:{}", functions_hit).unwrap();
+
+            for (line, branches) in &file.branches {
+                for (index, taken) in branches.iter().enumerate() {
+                    match taken {
+                        Some(count) => writeln!(out, "BRDA:{},0,{},{}", line, index, count).unwrap(),
+                        None => writeln!(out, "BRDA:{},0,{},-", line, index).unwrap(),
+                    }
+                }
+            }
+
+            let (branches_found, branches_hit) = file.get_branch_totals();
+            writeln!(out, "BRF:{}", branches_found).unwrap();
+            writeln!(out, "BRH:{}", branches_hit).unwrap();
+
+            for (line, count) in &file.lines {
+                writeln!(out, "DA:{},{}", line, count).unwrap();
+            }
+
+            let (lines_found, lines_hit) = file.get_line_totals();
+            writeln!(out, "LF:{}", lines_found).unwrap();
+            writeln!(out, "LH:{}", lines_hit).unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+
+        out
+    }
+
+    // Returns the index page followed by one page per source file, as (file name,
+    // contents) pairs.
+    pub fn to_html(&self, title: &str) -> Vec<(String, String)> {
+        let style = "<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+td, th { padding: 2px 8px; text-align: left; }
+pre { margin: 0; }
+.hit { background: #dfd; }
+.miss { background: #fdd; }
+.count { text-align: right; color: #666; }
+</style>";
+
+        let mut pages = vec![];
+        let mut index = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0} \
+             coverage</title>\n{1}\n</head>\n<body>\n<h1>{0} coverage</h1>\n<p>Lines: {2:.2}% &middot; Branches: \
+             {3:.2}%</p>\n<table>\n<tr><th>File</th><th>Lines</th><th>Branches</th></tr>\n",
+            escape_html(title),
+            style,
+            self.get_line_coverage(),
+            self.get_branch_coverage()
+        );
+
+        for (page_index, (path, file)) in self.files.iter().enumerate() {
+            let page_name = format!("file{}.html", page_index);
+            let (lines_found, lines_hit) = file.get_line_totals();
+            let (branches_found, branches_hit) = file.get_branch_totals();
+
+            writeln!(
+                index,
+                "<tr><td><a href=\"{}\">{}</a></td><td>{:.2}% ({}/{})</td><td>{:.2}% ({}/{})</td></tr>",
+                page_name,
+                escape_html(&path.display().to_string()),
+                percentage(lines_found, lines_hit),
+                lines_hit,
+                lines_found,
+                percentage(branches_found, branches_hit),
+                branches_hit,
+                branches_found
+            )
+            .unwrap();
+
+            let mut page = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta \
+                 charset=\"utf-8\">\n<title>{0}</title>\n{1}\n</head>\n<body>\n<h1>{0}</h1>\n<p><a \
+                 href=\"index.html\">Back to index</a></p>\n<table>\n",
+                escape_html(&path.display().to_string()),
+                style
+            );
+
+            let source = read_to_string(path).unwrap_or_default();
+            for (number, text) in source.lines().enumerate() {
+                let number = number as u32 + 1;
+                let (class, count) = match file.lines.get(&number) {
+                    Some(0) => ("miss", "0".to_string()),
+                    Some(c) => ("hit", c.to_string()),
+                    None => ("", String::new()),
+                };
+
+                writeln!(
+                    page,
+                    "<tr class=\"{}\"><td class=\"count\">{}</td><td \
+                     class=\"count\">{}</td><td><pre>{}</pre></td></tr>",
+                    class,
+                    number,
+                    count,
+                    escape_html(text)
+                )
+                .unwrap();
+            }
+
+            page.push_str("</table>\n</body>\n</html>\n");
+            pages.push((page_name, page));
+        }
+
+        index.push_str("</table>\n</body>\n</html>\n");
+        pages.insert(0, ("index.html".to_string(), index));
+
+        pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from gcc 12 for a C file with an `if` in main.
+    const C_OUTPUT: &str = "        -:    0:Source:src/main.c
+        -:    0:Graph:obj/coverage/main.gcno
+        -:    0:Data:obj/coverage/main.gcda
+        -:    0:Runs:1
+        -:    1:#include <stdio.h>
+        -:    2:
+function main called 1 returned 100% blocks executed 80%
+        1:    3:int main(int argc, char **argv) {
+        1:    4:    if (argc > 1)
+branch  0 taken 0 (fallthrough)
+branch  1 taken 1
+    #####:    5:        puts(argv[1]);
+        1:    6:    return 0;
+        -:    7:}
+";
+
+    // Captured from g++ 12 for a template instantiated with int and double.
+    const TEMPLATE_OUTPUT: &str = "        -:    0:Source:src/main.cpp
+        -:    0:Graph:obj/coverage/main.gcno
+        -:    0:Data:obj/coverage/main.gcda
+        -:    0:Runs:1
+        2:    1:template <typename T> T add(T a, T b) {
+        2:    2:    if (a > b)
+       1*:    3:        return a + b;
+       1*:    4:    return b;
+        -:    5:}
+------------------
+_Z3addIdET_S0_S0_:
+function _Z3addIdET_S0_S0_ called 1 returned 100% blocks executed 75%
+        1:    1:template <typename T> T add(T a, T b) {
+        1:    2:    if (a > b)
+branch  0 taken 1 (fallthrough)
+branch  1 taken 0
+        1:    3:        return a + b;
+    #####:    4:    return b;
+        -:    5:}
+------------------
+_Z3addIiET_S0_S0_:
+function _Z3addIiET_S0_S0_ called 1 returned 100% blocks executed 75%
+        1:    1:template <typename T> T add(T a, T b) {
+        1:    2:    if (a > b)
+branch  0 taken 0 (fallthrough)
+branch  1 taken 1
+    #####:    3:        return a + b;
+        1:    4:    return b;
+        -:    5:}
+------------------
+        -:    6:
+function main called 1 returned 100% blocks executed 100%
+        1:    7:int main() {
+        1:    8:    int x = add(1, 2);
+call    0 returned 1
+        1:    9:    double y = add(2.0, 1.0);
+call    0 returned 1
+        1:   10:    return x + (int) y > 100;
+        -:   11:}
+";
+
+    fn report(outputs: &[&str]) -> Report {
+        let mut report = Report::new();
+
+        for output in outputs {
+            report.add_gcov_output(output, Path::new("/project"));
+        }
+
+        report
+    }
+
+    #[test]
+    fn lines_branches_and_functions_are_parsed() {
+        let report = report(&[C_OUTPUT]);
+        let file = &report.files[Path::new("src/main.c")];
+
+        assert_eq!(
+            file.lines.iter().map(|(line, count)| (*line, *count)).collect::<Vec<_>>(),
+            [(3, 1), (4, 1), (5, 0), (6, 1)]
+        );
+        assert_eq!(file.branches[&4], [Some(0), Some(1)]);
+        assert_eq!(file.functions["main"], (3, 1));
+        assert_eq!(file.get_line_totals(), (4, 3));
+        assert_eq!(file.get_branch_totals(), (2, 1));
+    }
+
+    #[test]
+    fn template_instantiations_are_not_counted_twice() {
+        let report = report(&[TEMPLATE_OUTPUT]);
+        let file = &report.files[Path::new("src/main.cpp")];
+
+        assert_eq!(file.lines[&1], 2);
+        assert_eq!(file.lines[&3], 1);
+        assert_eq!(file.get_line_totals(), (8, 8));
+        assert_eq!(file.branches[&2], [Some(1), Some(1)]);
+        assert_eq!(file.functions["_Z3addIiET_S0_S0_"], (1, 1));
+        assert_eq!(file.functions["_Z3addIdET_S0_S0_"], (1, 1));
+        assert_eq!(file.functions["main"], (7, 1));
+    }
+
+    #[test]
+    fn units_sharing_a_file_are_merged() {
+        let report = report(&[C_OUTPUT, C_OUTPUT]);
+        let file = &report.files[Path::new("src/main.c")];
+
+        assert_eq!(file.lines[&3], 2);
+        assert_eq!(file.get_line_totals(), (4, 3));
+        assert_eq!(file.branches[&4], [Some(0), Some(2)]);
+        assert_eq!(file.functions["main"], (3, 2));
+    }
+
+    #[test]
+    fn files_outside_the_project_are_skipped() {
+        let output = C_OUTPUT.replace("Source:src/main.c", "Source:/usr/include/stdio.h");
+
+        assert!(report(&[&output]).is_empty());
+        assert_eq!(
+            report(&[&C_OUTPUT.replace("Source:src/main.c", "Source:/project/src/main.c")])
+                .files
+                .keys()
+                .collect::<Vec<_>>(),
+            [Path::new("src/main.c")]
+        );
+    }
+
+    #[test]
+    fn lcov_tracefiles_have_a_record_per_file() {
+        let lcov = report(&[C_OUTPUT]).to_lcov(Path::new("/project"));
+
+        assert_eq!(
+            lcov,
+            "TN:\nSF:/project/src/main.c\nFN:3,main\nFNDA:1,main\nFNF:1\nFNH:1\nBRDA:4,0,0,0\nBRDA:4,0,1,1\n\
+             BRF:2\nBRH:1\nDA:3,1\nDA:4,1\nDA:5,0\nDA:6,1\nLF:4\nLH:3\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn html_reports_have_an_index_and_a_page_per_file() {
+        let pages = report(&[C_OUTPUT, TEMPLATE_OUTPUT]).to_html("a<b");
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, ["index.html", "file0.html", "file1.html"]);
+        assert!(pages[0].1.contains("<title>a&lt;b coverage</title>"));
+        assert!(pages[0].1.contains("<a href=\"file0.html\">src/main.c</a></td><td>75.00% (3/4)"));
+        assert!(pages[0].1.contains("<a href=\"file1.html\">src/main.cpp</a></td><td>100.00% (8/8)"));
+    }
+
+    #[test]
+    fn empty_reports_are_fully_covered() {
+        assert_eq!(Report::new().get_line_coverage(), 100.0);
+        assert_eq!(Report::new().get_branch_coverage(), 100.0);
+    }
+}
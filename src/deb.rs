@@ -0,0 +1,319 @@
+use crate::{
+    common::*,
+    package::{get_timestamp, Tarball},
+    project::Project,
+};
+use std::{env, fmt::Write, path::PathBuf};
+
+// Returns the Debian name of the architecture that Ocean was built for, which
+// is also the one that it builds for.
+pub fn get_architecture() -> Result<&'static str, StrRet> {
+    match env::consts::ARCH {
+        "x86_64" => Ok("amd64"),
+        "x86" => Ok("i386"),
+        "aarch64" => Ok("arm64"),
+        "arm" => Ok("armhf"),
+        "powerpc64" => Ok("ppc64el"),
+        "riscv64" => Ok("riscv64"),
+        "s390x" => Ok("s390x"),
+        arch => Err(format!("There is no Debian architecture for \"{}\"", arch).into()),
+    }
+}
+
+// Debian package names may only use lowercase letters, digits and "+-.", and
+// must start with a letter or digit. Underscores are turned into hyphens.
+pub fn get_package_name(project: &Project) -> Result<String, StrRet> {
+    let name = project.get_name().to_lowercase().replace('_', "-");
+    let valid = name.len() >= 2
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c));
+
+    if !valid {
+        return Err(format!("\"{}\" is not a valid Debian package name", project.get_name()).into());
+    }
+
+    Ok(name)
+}
+
+// Semantic versions mark pre-releases with "-", which Debian would read as the
+// start of the revision. "~" sorts before the release, as "-" does in semver.
+pub fn get_version(project: &Project) -> String {
+    project
+        .get_version()
+        .map(|version| version.replacen('-', "~", 1))
+        .unwrap_or_else(|| "0.0.0".to_string())
+}
+
+// Returns the contents of the control file. The maintainer defaults to the
+// first of the project's authors. Installed-Size is in KiB, and
+// every line of the extended description has to start with a space, with "."
+// standing in for blank lines.
+fn generate_control(project: &Project, installed_size: u64) -> Result<String, StrRet> {
+    let options = project.get_deb_options();
+    let maintainer = match options.maintainer.clone().or_else(|| project.get_authors().first().cloned()) {
+        Some(maintainer) => maintainer,
+        None => return Err("No maintainer is set in the [deb] section of Ocean.toml, and there are no authors".into()),
+    };
+
+    let name = project.get_name();
+    let mut out = String::new();
+
+    writeln!(out, "Package: {}", get_package_name(project)?).unwrap();
+    writeln!(out, "Version: {}", get_version(project)).unwrap();
+    writeln!(out, "Architecture: {}", get_architecture()?).unwrap();
+    writeln!(out, "Maintainer: {}", maintainer).unwrap();
+    writeln!(out, "Installed-Size: {}", installed_size.div_ceil(1024)).unwrap();
+
+    if !options.depends.is_empty() {
+        writeln!(out, "Depends: {}", options.depends.join(", ")).unwrap();
+    }
+
+    writeln!(out, "Section: {}", options.section).unwrap();
+    writeln!(out, "Priority: {}", options.priority).unwrap();
+
+    if let Some(homepage) = project.get_homepage() {
+        writeln!(out, "Homepage: {}", homepage).unwrap();
+    }
+
+    let description = project.get_description().unwrap_or(name);
+    let mut lines = description.trim().lines();
+    writeln!(out, "Description: {}", lines.next().unwrap_or(name)).unwrap();
+
+    for line in lines {
+        match line.trim_end() {
+            "" => writeln!(out, " .").unwrap(),
+            line => writeln!(out, " {}", line).unwrap(),
+        }
+    }
+
+    Ok(out)
+}
+
+// Builds a Debian binary package: an ar archive of debian-binary, the control
+// archive (control and md5sums) and the data archive, in that order.
+pub fn build(project: &Project, data: &Tarball) -> Result<Vec<u8>, StrRet> {
+    let mut md5sums = String::new();
+    let mut installed_size = 0;
+
+    for (path, contents) in data.get_files() {
+        writeln!(md5sums, "{:x}  {}", md5::compute(contents), path.display()).unwrap();
+        installed_size += contents.len() as u64;
+    }
+
+    let mut control = Tarball::default();
+    control.add_data(
+        PathBuf::from("control"),
+        generate_control(project, installed_size)?.into_bytes(),
+        0o644,
+    );
+    control.add_data(PathBuf::from("md5sums"), md5sums.into_bytes(), 0o644);
+
+    Ok(write_ar(&[
+        ("debian-binary", &b"2.0\n"[..]),
+        ("control.tar.gz", &control.to_tar_gz()?),
+        ("data.tar.gz", &data.to_tar_gz()?),
+    ]))
+}
+
+// Writes a common ar archive. Like the tarballs, every member is owned by root
+// and has the same timestamp.
+fn write_ar(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let timestamp = get_timestamp();
+    let mut out = b"!<arch>\n".to_vec();
+
+    for (name, contents) in members {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8o}{:<10}`\n",
+            name,
+            timestamp,
+            0,
+            0,
+            0o100644,
+            contents.len()
+        );
+
+        out.extend(header.bytes());
+        out.extend_from_slice(contents);
+
+        if contents.len() % 2 == 1 {
+            out.push(b'\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tar::Archive;
+
+    fn get_project(extra: &str) -> Project {
+        toml::from_str(&format!(
+            "[project]
+name = 'demo_tool'
+version = '1.2.0-beta.1'
+authors = ['Jane Doe <jane@example.com>', 'John Doe <john@example.com>']
+language = 'C'
+libraries = []
+library_directories = []
+{}
+
+[directories]
+build_dir = './build'
+source_dir = './src'
+object_dir = './obj'
+
+[compiler.c]
+command = 'gcc'
+flags = []
+
+[compiler.cxx]
+command = 'g++'
+flags = []
+",
+            extra
+        ))
+        .unwrap()
+    }
+
+    // Splits an ar archive into its members, checking the header of each.
+    fn read_ar(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        assert!(archive.starts_with(b"!<arch>\n"));
+
+        let mut members = vec![];
+        let mut offset = 8;
+
+        while offset < archive.len() {
+            assert_eq!(offset % 2, 0, "members start at even offsets");
+
+            let header = std::str::from_utf8(&archive[offset..offset + 60]).unwrap();
+            assert_eq!(&header[58..], "`\n");
+            assert_eq!(header[16..28].trim_end(), get_timestamp().to_string());
+            assert_eq!(&header[28..48], "0     0     100644  ");
+
+            let size: usize = header[48..58].trim_end().parse().unwrap();
+            let start = offset + 60;
+            members.push((header[..16].trim_end().to_string(), archive[start..start + size].to_vec()));
+
+            offset = start + size + size % 2;
+        }
+
+        assert_eq!(offset, archive.len());
+        members
+    }
+
+    fn read_tar_gz(tar_gz: &[u8]) -> Vec<(String, String)> {
+        let mut archive = Archive::new(GzDecoder::new(tar_gz));
+
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents).unwrap();
+
+                (entry.path().unwrap().to_string_lossy().into_owned(), contents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ar_members_are_padded_to_even_offsets() {
+        let archive = write_ar(&[("debian-binary", b"2.0\n"), ("odd", b"abc"), ("empty", b"")]);
+
+        assert_eq!(archive.len(), 8 + (60 + 4) + (60 + 3 + 1) + 60);
+        assert_eq!(&archive[8..24], b"debian-binary   ");
+        assert_eq!(&archive[56..68], b"4         `\n");
+        assert_eq!(&archive[68..72], b"2.0\n");
+        assert_eq!(&archive[132..136], b"abc\n");
+        assert_eq!(
+            read_ar(&archive),
+            [
+                ("debian-binary".to_string(), b"2.0\n".to_vec()),
+                ("odd".to_string(), b"abc".to_vec()),
+                ("empty".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn packages_hold_the_control_and_data_archives_in_order() {
+        let mut data = Tarball::default();
+        data.add_data(PathBuf::from("usr/bin/demo-tool"), b"binary".to_vec(), 0o755);
+        data.add_data(PathBuf::from("usr/share/doc/demo-tool/README"), b"odd".to_vec(), 0o644);
+
+        let project = get_project("description = 'A demo'");
+        let members = read_ar(&build(&project, &data).unwrap());
+        let names: Vec<&str> = members.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, ["debian-binary", "control.tar.gz", "data.tar.gz"]);
+        assert_eq!(members[0].1, b"2.0\n");
+        assert_eq!(members[2].1, data.to_tar_gz().unwrap());
+
+        let control = read_tar_gz(&members[1].1);
+        assert_eq!(control[0], ("control".to_string(), generate_control(&project, 9).unwrap()));
+        assert_eq!(
+            control[1],
+            (
+                "md5sums".to_string(),
+                format!(
+                    "{:x}  usr/bin/demo-tool\n{:x}  usr/share/doc/demo-tool/README\n",
+                    md5::compute(b"binary"),
+                    md5::compute(b"odd")
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn control_fields_are_rendered() {
+        let project = get_project(
+            "description = \"\"\"A demo tool.\nIt does things.\n\nAnd more things.\"\"\"
+homepage = 'https://example.com/demo'
+
+[deb]
+depends = ['libc6 (>= 2.31)', 'zlib1g']
+section = 'utils'",
+        );
+
+        assert_eq!(
+            generate_control(&project, 2049).unwrap(),
+            format!(
+                "Package: demo-tool
+Version: 1.2.0~beta.1
+Architecture: {}
+Maintainer: Jane Doe <jane@example.com>
+Installed-Size: 3
+Depends: libc6 (>= 2.31), zlib1g
+Section: utils
+Priority: optional
+Homepage: https://example.com/demo
+Description: A demo tool.
+ It does things.
+ .
+ And more things.
+",
+                get_architecture().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn maintainer_is_required() {
+        let mut project = get_project("");
+        project.set_authors(vec![]);
+
+        assert!(generate_control(&project, 0).is_err());
+
+        let project = get_project("\n[deb]\nmaintainer = 'Packager <pkg@example.com>'");
+        let control = generate_control(&project, 0).unwrap();
+
+        assert!(control.contains("Maintainer: Packager <pkg@example.com>\n"));
+        assert!(control.contains("Installed-Size: 0\n"));
+        assert!(control.contains("Description: demo_tool\n"));
+    }
+}
@@ -189,7 +189,7 @@ impl VsCode {
             \"name\": \"Debug\",
             \"type\": \"cppdbg\",
             \"request\": \"launch\",
-            \"program\": \"${{workspaceFolder}}/build/debug/{}\",
+            \"program\": \"${{workspaceFolder}}/build/debug/{}{}\",
             \"args\": [],
             \"stopAtEntry\": false,
             \"cwd\": \"${{workspaceFolder}}\",
@@ -207,11 +207,8 @@ impl VsCode {
         }}
     ]
 }}",
-                format!(
-                    "{}{}",
-                    project.get_name(),
-                    if env::consts::OS == "windows" { ".exe" } else { "" }
-                )
+                project.get_name(),
+                if env::consts::OS == "windows" { ".exe" } else { "" }
             ),
         );
 
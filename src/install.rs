@@ -0,0 +1,260 @@
+use crate::common::*;
+use std::{
+    fs::{copy, create_dir_all, read_to_string, remove_dir, remove_file, File},
+    io::prelude::*,
+    path::{Component, Path, PathBuf},
+};
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::{fs::set_permissions, os::unix::fs::PermissionsExt};
+
+    set_permissions(path, PermissionsExt::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &Path, _: u32) -> std::io::Result<()> { Ok(()) }
+
+// A file that `ocean install` copies to `directory` (before staging) as
+// `name`, with the given Unix permissions.
+pub struct InstallFile {
+    pub source: PathBuf,
+    pub directory: PathBuf,
+    pub name: PathBuf,
+    pub mode: u32,
+}
+
+// Copies files into an install tree and records every file and directory it
+// creates below the prefix, so that the manifest it writes can be used to
+// uninstall exactly what was installed. Paths are recorded as they will be on
+// the target system, and only staged where they are read or written.
+pub struct Installer {
+    prefix: PathBuf,
+    destdir: Option<PathBuf>,
+    files: Vec<PathBuf>,
+    directories: Vec<PathBuf>,
+}
+
+impl Installer {
+    pub fn new(prefix: PathBuf, destdir: Option<PathBuf>) -> Self {
+        Self {
+            prefix,
+            destdir,
+            files: vec![],
+            directories: vec![],
+        }
+    }
+
+    // Carries over whatever an earlier install left in place, so that
+    // installing again, possibly somewhere else, does not forget to remove it
+    // on uninstall. `manifest_path` is read as it is, without staging it.
+    pub fn keep_previous(&mut self, manifest_path: &Path) {
+        let manifest = read_to_string(manifest_path).unwrap_or_default();
+
+        for line in manifest.lines().filter(|line| !line.is_empty()) {
+            match line.strip_suffix('/') {
+                Some(directory) if self.get_staged_path(Path::new(directory)).is_dir() =>
+                    self.directories.push(PathBuf::from(directory)),
+                None if self.get_staged_path(Path::new(line)).is_file() => self.files.push(PathBuf::from(line)),
+                _ => (),
+            }
+        }
+    }
+
+    // Puts `path` below the staging directory, if there is one, the way
+    // `make DESTDIR=... install` does.
+    pub fn get_staged_path(&self, path: &Path) -> PathBuf {
+        match &self.destdir {
+            Some(destdir) => destdir.join(
+                path.components()
+                    .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)))
+                    .collect::<PathBuf>(),
+            ),
+            None => path.to_path_buf(),
+        }
+    }
+
+    // Creates `directory`, which is not staged yet, and records the ones that
+    // were missing below the prefix. The prefix itself, and anything above it,
+    // is never removed on uninstall.
+    fn create_directory(&mut self, directory: &Path) -> Result<(), StrRet> {
+        let mut missing: Vec<PathBuf> = directory
+            .ancestors()
+            .take_while(|ancestor| *ancestor != self.prefix && ancestor.starts_with(&self.prefix))
+            .filter(|ancestor| !self.get_staged_path(ancestor).exists())
+            .map(Path::to_path_buf)
+            .collect();
+        let staged = self.get_staged_path(directory);
+
+        if let Err(e) = create_dir_all(&staged) {
+            return Err(format!("Could not create directory \"{}\": {}", staged.display(), e).into());
+        }
+
+        missing.reverse();
+        self.directories.extend(missing);
+
+        Ok(())
+    }
+
+    pub fn install_file(&mut self, file: &InstallFile) -> Result<(), StrRet> {
+        let InstallFile {
+            source,
+            directory,
+            name,
+            mode,
+        } = file;
+        let destination = directory.join(name);
+        let staged = self.get_staged_path(&destination);
+
+        if let Some(parent) = destination.parent() {
+            self.create_directory(parent)?;
+        }
+
+        println!("Installing {}", staged.display());
+
+        // Removing the old file first lets a running executable be replaced.
+        remove_file(&staged).unwrap_or(());
+
+        if let Err(e) = copy(source, &staged).and_then(|_| set_mode(&staged, *mode)) {
+            return Err(format!("Could not install \"{}\": {}", source.display(), e).into());
+        }
+
+        if !self.files.contains(&destination) {
+            self.files.push(destination);
+        }
+
+        Ok(())
+    }
+
+    // Directories are written with a trailing slash, after the files, in the
+    // order they were created. `path` is not staged yet, and the manifest lists
+    // itself so that uninstalling removes it along with its directory.
+    pub fn write_manifest(&mut self, path: &Path) -> Result<(), StrRet> {
+        if let Some(parent) = path.parent() {
+            self.create_directory(parent)?;
+        }
+
+        if !self.files.iter().any(|file| file == path) {
+            self.files.push(path.to_path_buf());
+        }
+
+        let mut contents = String::new();
+
+        for file in &self.files {
+            contents.push_str(&format!("{}\n", file.display()));
+        }
+
+        for directory in &self.directories {
+            contents.push_str(&format!("{}/\n", directory.display()));
+        }
+
+        match File::create(self.get_staged_path(path)).and_then(|mut file| file.write_all(contents.as_bytes())) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write install manifest: {}", e).into()),
+        }
+    }
+
+    // Removes everything listed in the install manifest at `manifest_path`,
+    // which is read as it is, below the staging directory. Directories are
+    // only removed once they are empty, innermost first. Returns the number of
+    // files removed.
+    pub fn uninstall(&self, manifest_path: &Path) -> Result<usize, StrRet> {
+        let manifest = match read_to_string(manifest_path) {
+            Ok(manifest) => manifest,
+            Err(e) =>
+                return Err(format!(
+                    "Could not read install manifest \"{}\": {}. Has the project been installed?",
+                    manifest_path.display(),
+                    e
+                )
+                .into()),
+        };
+
+        let mut removed = 0;
+
+        for line in manifest.lines().filter(|line| !line.is_empty() && !line.ends_with('/')) {
+            let path = self.get_staged_path(Path::new(line));
+
+            // The manifest lists itself, but is not one of the project's files.
+            if path == manifest_path {
+                remove_file(&path).unwrap_or(());
+                continue;
+            }
+
+            println!("Removing {}", path.display());
+
+            match remove_file(&path) {
+                Ok(_) => removed += 1,
+                Err(e) => eprintln!("Could not remove \"{}\": {}", path.display(), e),
+            }
+        }
+
+        for line in manifest.lines().rev().filter(|line| line.ends_with('/')) {
+            remove_dir(self.get_staged_path(Path::new(line))).unwrap_or(());
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs::remove_dir_all, fs::write, process};
+
+    #[test]
+    fn staged_manifests_list_paths_on_the_target_system() {
+        let directory = env::temp_dir().join(format!("ocean-install-test-{}", process::id()));
+        let stage = directory.join("stage");
+        let source = directory.join("demo");
+        let prefix = PathBuf::from("/usr/local");
+        let manifest_path = prefix.join("share/ocean/demo/install_manifest.txt");
+
+        create_dir_all(&directory).unwrap();
+        write(&source, "#!/bin/sh\n").unwrap();
+
+        let mut installer = Installer::new(prefix.clone(), Some(stage.clone()));
+        installer
+            .install_file(&InstallFile {
+                source: source.clone(),
+                directory: prefix.join("bin"),
+                name: PathBuf::from("demo"),
+                mode: 0o755,
+            })
+            .unwrap();
+        installer.write_manifest(&manifest_path).unwrap();
+
+        assert!(stage.join("usr/local/bin/demo").is_file());
+        assert_eq!(
+            read_to_string(stage.join("usr/local/share/ocean/demo/install_manifest.txt")).unwrap(),
+            "/usr/local/bin/demo
+/usr/local/share/ocean/demo/install_manifest.txt
+/usr/local/bin/
+/usr/local/share/
+/usr/local/share/ocean/
+/usr/local/share/ocean/demo/
+"
+        );
+
+        // Installing again keeps the same entries, without listing the
+        // directories that exist now as created a second time.
+        let mut again = Installer::new(prefix.clone(), Some(stage.clone()));
+        again.keep_previous(&again.get_staged_path(&manifest_path));
+        again.write_manifest(&manifest_path).unwrap();
+        assert_eq!(
+            read_to_string(stage.join("usr/local/share/ocean/demo/install_manifest.txt"))
+                .unwrap()
+                .lines()
+                .count(),
+            6
+        );
+
+        let removed = again.uninstall(&again.get_staged_path(&manifest_path)).unwrap();
+        assert_eq!(removed, 1);
+        assert!(!stage.join("usr/local/bin").exists());
+        assert!(!stage.join("usr/local/share").exists());
+        assert!(stage.join("usr/local").is_dir());
+
+        remove_dir_all(directory).unwrap();
+    }
+}
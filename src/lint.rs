@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+pub struct Diagnostic {
+    pub location: String,
+    pub severity: String,
+    pub message: String,
+    pub details: Vec<String>,
+}
+
+// Splits clang-tidy output into diagnostics grouped by the file they refer to.
+// Lines that do not start a new diagnostic (source excerpts, fix-it hints) are
+// kept with the diagnostic before them, and summary lines such as "3 warnings
+// generated." are dropped.
+pub fn parse_diagnostics(output: &str) -> BTreeMap<String, Vec<Diagnostic>> {
+    let mut files: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+    let mut last_file: Option<String> = None;
+
+    for line in output.lines() {
+        if let Some((path, diagnostic)) = parse_line(line) {
+            last_file = Some(path.clone());
+            files.entry(path).or_default().push(diagnostic);
+            continue;
+        }
+
+        if line.ends_with(" generated.") || line.starts_with("Suppressed ") || line.starts_with("Use -header-filter") {
+            continue;
+        }
+
+        if let Some(diagnostic) = last_file
+            .as_ref()
+            .and_then(|path| files.get_mut(path))
+            .and_then(|diagnostics| diagnostics.last_mut())
+        {
+            diagnostic.details.push(line.to_string());
+        }
+    }
+
+    files
+}
+
+fn parse_line(line: &str) -> Option<(String, Diagnostic)> {
+    for severity in &["warning", "error", "note"] {
+        let marker = format!(": {}: ", severity);
+
+        if let Some(position) = line.find(&marker) {
+            let location = &line[..position];
+            let mut parts = location.rsplitn(3, ':');
+            let column = parts.next()?;
+            let row = parts.next()?;
+            let path = parts.next()?;
+
+            if column.parse::<u32>().is_err() || row.parse::<u32>().is_err() {
+                return None;
+            }
+
+            return Some((
+                path.to_string(),
+                Diagnostic {
+                    location: format!("{}:{}", row, column),
+                    severity: severity.to_string(),
+                    message: line[position + marker.len()..].to_string(),
+                    details: vec![],
+                },
+            ));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "3 warnings generated.
+/project/src/main.c:4:5: warning: Call to function 'strcpy' is insecure [clang-analyzer-security.insecureAPI.strcpy]
+    strcpy(buffer, argv[1]);
+    ^~~~~~
+/project/src/main.c:4:5: note: Call to function 'strcpy' is insecure as it does not provide bounding
+/project/src/util.h:2:12: error: unknown type name 'size' [clang-diagnostic-error]
+int length(size value);
+           ^
+Suppressed 12 warnings (12 in non-user code).
+Use -header-filter=.* to display errors from all non-system headers.
+";
+
+    #[test]
+    fn diagnostics_are_grouped_by_file() {
+        let files = parse_diagnostics(OUTPUT);
+        let paths: Vec<&str> = files.keys().map(String::as_str).collect();
+
+        assert_eq!(paths, ["/project/src/main.c", "/project/src/util.h"]);
+
+        let main = &files["/project/src/main.c"];
+        assert_eq!(main.len(), 2);
+        assert_eq!(main[0].location, "4:5");
+        assert_eq!(main[0].severity, "warning");
+        assert_eq!(
+            main[0].message,
+            "Call to function 'strcpy' is insecure [clang-analyzer-security.insecureAPI.strcpy]"
+        );
+        assert_eq!(main[0].details, ["    strcpy(buffer, argv[1]);", "    ^~~~~~"]);
+        assert_eq!(main[1].severity, "note");
+
+        let util = &files["/project/src/util.h"];
+        assert_eq!(util[0].severity, "error");
+        assert_eq!(util[0].message, "unknown type name 'size' [clang-diagnostic-error]");
+        assert_eq!(util[0].details, ["int length(size value);", "           ^"]);
+    }
+
+    #[test]
+    fn paths_with_colons_keep_them() {
+        let files = parse_diagnostics("C:\\project\\main.c:10:2: warning: unused variable 'x' [unused]");
+
+        assert_eq!(files["C:\\project\\main.c"][0].location, "10:2");
+    }
+
+    #[test]
+    fn lines_without_a_location_are_not_diagnostics() {
+        assert!(parse_diagnostics("error: no input files\n1 warning generated.\n").is_empty());
+    }
+}
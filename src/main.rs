@@ -1,13 +1,28 @@
-#![allow(clippy::pedantic)]
+#![allow(clippy::pedantic, clippy::upper_case_acronyms)]
 
+mod assets;
+mod build_script;
 mod cache;
+mod checks;
 mod commands;
 mod common;
 mod compiler;
+mod config_header;
+mod coverage;
+mod deb;
 mod editors;
+mod install;
 mod language;
+mod lint;
+mod metadata;
+mod package;
+mod pkg_config;
 mod platform;
 mod project;
+mod remote_cache;
+mod resources;
+mod shared_cache;
+mod spdx;
 
 use commands::Commands;
 use common::StrRet;
@@ -25,7 +40,11 @@ fn parse_args(mut args: Vec<String>) -> Result<(), StrRet> {
 
     match args[0].as_str() {
         "build" => Commands::build(&args[1..])?,
+        "cache" => Commands::cache(&args[1..])?,
+        "check" => Commands::check(&args[1..])?,
         "clean" => Commands::clean()?,
+        "coverage" => Commands::coverage(&args[1..])?,
+        "fmt" => Commands::fmt(&args[1..])?,
         "get" =>
             if !args[1..].is_empty() && platforms.contains(&args[1].as_str()) {
                 Commands::get_data_platform(&args[2..], args[1].clone())?;
@@ -33,7 +52,10 @@ fn parse_args(mut args: Vec<String>) -> Result<(), StrRet> {
                 Commands::get_data(&args[1..])?;
             },
         "help" | "--help" => Commands::help(None),
+        "install" => Commands::install(&args[1..])?,
+        "lint" => Commands::lint(&args[1..])?,
         "new" => Commands::new_project(&args[1..])?,
+        "package" => Commands::package(&args[1..])?,
         "run" => Commands::run(&args[1..])?,
         "set" =>
             if !args[1..].is_empty() && platforms.contains(&args[1].as_str()) {
@@ -41,6 +63,8 @@ fn parse_args(mut args: Vec<String>) -> Result<(), StrRet> {
             } else {
                 Commands::set_data(&args[1..])?;
             },
+        "uninstall" => Commands::uninstall(&args[1..])?,
+        "watch" => Commands::watch(&args[1..])?,
         _ => Commands::help(Some(&args[0])),
     };
 
@@ -52,5 +76,6 @@ fn main() {
 
     if let Err(e) = parse_args(args) {
         eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
 }
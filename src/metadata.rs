@@ -0,0 +1,189 @@
+use crate::{
+    common::*,
+    spdx::{EXCEPTIONS, LICENSES},
+};
+
+fn is_numeric_identifier(part: &str) -> bool {
+    !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) && (part == "0" || !part.starts_with('0'))
+}
+
+fn is_identifier(part: &str) -> bool {
+    !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// Checks that `version` is a semantic version (https://semver.org), such as
+// 1.2.3, 1.0.0-rc.1 or 2.0.0+20240101.
+pub fn validate_version(version: &str) -> Result<(), StrRet> {
+    let (version_core, build) = match version.split_once('+') {
+        Some((version_core, build)) => (version_core, Some(build)),
+        None => (version, None),
+    };
+    let (version_core, pre_release) = match version_core.split_once('-') {
+        Some((version_core, pre_release)) => (version_core, Some(pre_release)),
+        None => (version_core, None),
+    };
+
+    let numbers: Vec<&str> = version_core.split('.').collect();
+    let valid = numbers.len() == 3
+        && numbers.iter().all(|number| is_numeric_identifier(number))
+        && pre_release.is_none_or(|pre_release| {
+            pre_release.split('.').all(|part| {
+                is_identifier(part) && (!part.chars().all(|c| c.is_ascii_digit()) || is_numeric_identifier(part))
+            })
+        })
+        && build.is_none_or(|build| build.split('.').all(is_identifier));
+
+    if !valid {
+        return Err(format!(
+            "\"{}\" is not a valid version, it should be a semantic version such as 1.0.0",
+            version
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+fn is_license(token: &str) -> bool {
+    let token = token.strip_suffix('+').unwrap_or(token);
+    let custom = token
+        .strip_prefix("LicenseRef-")
+        .or_else(|| token.split_once(":LicenseRef-").map(|(_, custom)| custom));
+
+    match custom {
+        Some(custom) => !custom.is_empty() && custom.chars().all(|c| c.is_ascii_alphanumeric() || "-.".contains(c)),
+        None => LICENSES.iter().any(|license| license.eq_ignore_ascii_case(token)),
+    }
+}
+
+// Parses the licence expression in `tokens` that starts at `index`, and returns
+// the index of the first token after it.
+fn parse_license_expression(tokens: &[String], mut index: usize) -> Result<usize, String> {
+    loop {
+        index = match tokens.get(index).map(String::as_str) {
+            Some("(") => {
+                let end = parse_license_expression(tokens, index + 1)?;
+
+                match tokens.get(end).map(String::as_str) {
+                    Some(")") => end + 1,
+                    _ => return Err("missing \")\"".to_string()),
+                }
+            },
+            Some(token) if is_license(token) => match tokens.get(index + 1).map(String::as_str) {
+                Some("WITH" | "with") => match tokens.get(index + 2) {
+                    Some(exception) if EXCEPTIONS.iter().any(|e| e.eq_ignore_ascii_case(exception)) => index + 3,
+                    Some(exception) => return Err(format!("\"{}\" is not a known licence exception", exception)),
+                    None => return Err("missing licence exception after WITH".to_string()),
+                },
+                _ => index + 1,
+            },
+            Some(token) => return Err(format!("\"{}\" is not a known SPDX licence identifier", token)),
+            None => return Err("missing licence identifier".to_string()),
+        };
+
+        match tokens.get(index).map(String::as_str) {
+            Some("AND" | "and" | "OR" | "or") => index += 1,
+            _ => return Ok(index),
+        }
+    }
+}
+
+// Checks that `license` is an SPDX licence expression, such as MIT,
+// "MIT OR Apache-2.0" or "GPL-2.0-or-later WITH Classpath-exception-2.0".
+pub fn validate_license(license: &str) -> Result<(), StrRet> {
+    let tokens: Vec<String> = license
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+
+    let result = match parse_license_expression(&tokens, 0) {
+        Ok(end) if end < tokens.len() => Err(format!("unexpected \"{}\"", tokens[end])),
+        result => result.map(|_| ()),
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!(
+            "\"{}\" is not a valid SPDX licence expression: {}. Use LicenseRef-<name> for other licences",
+            license, e
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn semantic_versions_are_accepted() {
+        for version in ["0.0.0", "1.2.3", "10.20.30", "1.0.0-alpha", "1.0.0-rc.1", "1.0.0-0.3.7", "2.0.0+20240101"] {
+            assert!(validate_version(version).is_ok(), "{}", version);
+        }
+
+        assert!(validate_version("1.0.0-beta.11+exp.sha.5114f85").is_ok());
+    }
+
+    #[test]
+    fn invalid_versions_are_rejected() {
+        for version in [
+            "",
+            "1",
+            "1.0",
+            "1.0.0.0",
+            "01.0.0",
+            "1.02.0",
+            "1.0.0-",
+            "1.0.0-01",
+            "1.0.0+",
+            "1.0.0-a..b",
+            "v1.0.0",
+        ] {
+            assert!(validate_version(version).is_err(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn licence_expressions_are_accepted() {
+        for license in [
+            "MIT",
+            "mit",
+            "MIT OR Apache-2.0",
+            "(MIT OR Apache-2.0) AND BSD-3-Clause",
+            "GPL-2.0-or-later WITH Classpath-exception-2.0",
+            "Apache-2.0 WITH LLVM-exception",
+            "LGPL-2.1+",
+            "LicenseRef-Proprietary",
+            "DocumentRef-spdx-tool:LicenseRef-Custom.1",
+        ] {
+            assert!(validate_license(license).is_ok(), "{}", license);
+        }
+    }
+
+    #[test]
+    fn deprecated_licences_are_accepted() {
+        for license in ["GPL-2.0", "GPL-3.0", "LGPL-2.1", "GPL-2.0-with-classpath-exception", "wxWindows"] {
+            assert!(validate_license(license).is_ok(), "{}", license);
+        }
+    }
+
+    #[test]
+    fn invalid_licence_expressions_are_rejected() {
+        for license in [
+            "",
+            "Foo",
+            "MIT OR",
+            "MIT Apache-2.0",
+            "(MIT",
+            "MIT)",
+            "MIT WITH",
+            "MIT WITH Foo-exception",
+            "LicenseRef-",
+            "AND MIT",
+        ] {
+            assert!(validate_license(license).is_err(), "{}", license);
+        }
+    }
+}
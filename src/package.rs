@@ -0,0 +1,211 @@
+use crate::common::*;
+use flate2::{Compression, GzBuilder};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::read,
+    io::prelude::*,
+    path::{Path, PathBuf},
+};
+use tar::{Builder, EntryType, Header};
+
+// Every entry gets the same timestamp so that packaging the same files twice
+// gives the same bytes. SOURCE_DATE_EPOCH overrides it, as it does for other
+// reproducible build tools.
+pub fn get_timestamp() -> u64 {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or(0)
+}
+
+// Source files are archived as 0644, or 0755 if they are executable, whatever
+// the umask was when they were checked out.
+#[cfg(unix)]
+pub fn get_archive_mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    match path.metadata() {
+        Ok(metadata) if metadata.permissions().mode() & 0o111 != 0 => 0o755,
+        _ => 0o644,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn get_archive_mode(_: &Path) -> u32 { 0o644 }
+
+// A tar archive that is built in memory. Files are written sorted by path,
+// each preceded by any parent directories that have not been written yet, and
+// every entry is owned by root with a fixed timestamp.
+#[derive(Default)]
+pub struct Tarball {
+    files: BTreeMap<PathBuf, (Vec<u8>, u32)>,
+}
+
+impl Tarball {
+    pub fn add_data(&mut self, path: PathBuf, contents: Vec<u8>, mode: u32) {
+        self.files.insert(path, (contents, mode));
+    }
+
+    pub fn add_file(&mut self, path: PathBuf, source: &Path, mode: u32) -> Result<(), StrRet> {
+        match read(source) {
+            Ok(contents) => {
+                self.add_data(path, contents, mode);
+                Ok(())
+            },
+            Err(e) => Err(format!("Could not read \"{}\": {}", source.display(), e).into()),
+        }
+    }
+
+    pub fn get_files(&self) -> impl Iterator<Item = (&PathBuf, &Vec<u8>)> {
+        self.files.iter().map(|(path, (contents, _))| (path, contents))
+    }
+
+    pub fn to_tar(&self) -> Result<Vec<u8>, StrRet> {
+        let mut entries: BTreeMap<&Path, Option<&(Vec<u8>, u32)>> = BTreeMap::new();
+
+        for (path, file) in &self.files {
+            for directory in path.ancestors().skip(1) {
+                if directory.as_os_str().is_empty() || directory == Path::new(".") {
+                    break;
+                }
+
+                entries.insert(directory, None);
+            }
+
+            entries.insert(path, Some(file));
+        }
+
+        let timestamp = get_timestamp();
+        let mut builder = Builder::new(vec![]);
+
+        for (path, file) in entries {
+            let mut header = Header::new_gnu();
+            header.set_mtime(timestamp);
+            header.set_uid(0);
+            header.set_gid(0);
+
+            if let Err(e) = header.set_username("root").and_then(|_| header.set_groupname("root")) {
+                return Err(format!("Could not write archive header: {}", e).into());
+            }
+
+            let contents: &[u8] = match file {
+                Some((contents, mode)) => {
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_mode(*mode);
+                    contents
+                },
+                None => {
+                    header.set_entry_type(EntryType::Directory);
+                    header.set_mode(0o755);
+                    &[]
+                },
+            };
+
+            header.set_size(contents.len() as u64);
+
+            if let Err(e) = builder.append_data(&mut header, path, contents) {
+                return Err(format!("Could not add \"{}\" to the archive: {}", path.display(), e).into());
+            }
+        }
+
+        match builder.into_inner() {
+            Ok(tar) => Ok(tar),
+            Err(e) => Err(format!("Could not write archive: {}", e).into()),
+        }
+    }
+
+    // The gzip header is written without a file name and with a zero
+    // timestamp, so it does not change between runs either.
+    pub fn to_tar_gz(&self) -> Result<Vec<u8>, StrRet> {
+        let tar = self.to_tar()?;
+        let mut encoder = GzBuilder::new().write(vec![], Compression::best());
+
+        match encoder.write_all(&tar).and_then(|_| encoder.finish()) {
+            Ok(tar_gz) => Ok(tar_gz),
+            Err(e) => Err(format!("Could not compress archive: {}", e).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use tar::Archive;
+
+    fn get_tarball(files: &[(&str, &str, u32)]) -> Tarball {
+        let mut tarball = Tarball::default();
+
+        for (path, contents, mode) in files {
+            tarball.add_data(PathBuf::from(path), contents.as_bytes().to_vec(), *mode);
+        }
+
+        tarball
+    }
+
+    #[test]
+    fn archives_are_reproducible() {
+        let files = [
+            ("demo-1.0.0/src/main.c", "int main(void) { return 0; }\n", 0o644),
+            ("demo-1.0.0/Ocean.toml", "[project]\nname = 'demo'\n", 0o644),
+            ("demo-1.0.0/build.sh", "#!/bin/sh\n", 0o755),
+        ];
+        let mut reversed = files;
+        reversed.reverse();
+
+        let first = get_tarball(&files).to_tar_gz().unwrap();
+        let second = get_tarball(&reversed).to_tar_gz().unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(get_tarball(&files).to_tar_gz().unwrap(), first);
+
+        let mut archive = Archive::new(GzDecoder::new(&first[..]));
+        let mut paths = vec![];
+
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let header = entry.header();
+
+            assert_eq!(header.mtime().unwrap(), get_timestamp());
+            assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+            assert_eq!(header.username().unwrap(), Some("root"));
+            assert_eq!(header.groupname().unwrap(), Some("root"));
+
+            let path = entry.path().unwrap().into_owned();
+            let expected_mode = match path.to_str().unwrap() {
+                "demo-1.0.0/build.sh" => 0o755,
+                _ if header.entry_type() == EntryType::Directory => 0o755,
+                _ => 0o644,
+            };
+            assert_eq!(header.mode().unwrap(), expected_mode);
+
+            paths.push(path);
+        }
+
+        // Directories come before the files in them, and everything is sorted.
+        assert_eq!(
+            paths,
+            [
+                "demo-1.0.0",
+                "demo-1.0.0/Ocean.toml",
+                "demo-1.0.0/build.sh",
+                "demo-1.0.0/src",
+                "demo-1.0.0/src/main.c"
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn gzip_header_has_no_name_or_timestamp() {
+        let tar_gz = get_tarball(&[("demo/a.c", "", 0o644)]).to_tar_gz().unwrap();
+
+        assert_eq!(&tar_gz[..3], &[0x1f, 0x8b, 8]);
+        // No FNAME flag, and a modification time of zero.
+        assert_eq!(tar_gz[3] & 0x08, 0);
+        assert_eq!(&tar_gz[4..8], &[0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::project::Project;
+use std::{fmt::Write, path::Path};
+
+// Writes `directory` relative to `${prefix}` when it is inside the prefix, so
+// that the file still works when the install is relocated.
+fn relative_to_prefix(directory: &Path, prefix: &Path) -> String {
+    match directory.strip_prefix(prefix) {
+        Ok(relative) if relative.as_os_str().is_empty() => "${prefix}".to_string(),
+        Ok(relative) => format!("${{prefix}}/{}", relative.display()),
+        Err(_) => directory.display().to_string(),
+    }
+}
+
+// Returns the contents of `<name>.pc` for a library installed with the given
+// directories. The libraries the project links against are only needed when
+// linking statically, so they go in Libs.private. Relative library
+// directories only make sense inside the project, so they are left out.
+pub fn generate(project: &Project, prefix: &Path, libdir: &Path, includedir: &Path) -> String {
+    let options = project.get_pkg_config_options();
+    let name = project.get_name();
+    let mut out = String::new();
+
+    writeln!(out, "prefix={}", prefix.display()).unwrap();
+    writeln!(out, "libdir={}", relative_to_prefix(libdir, prefix)).unwrap();
+    writeln!(out, "includedir={}", relative_to_prefix(includedir, prefix)).unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "Name: {}", name).unwrap();
+    writeln!(out, "Description: {}", project.get_description().unwrap_or(name)).unwrap();
+    writeln!(
+        out,
+        "Version: {}",
+        project.get_version().map(String::as_str).unwrap_or("0.0.0")
+    )
+    .unwrap();
+
+    if let Some(homepage) = project.get_homepage() {
+        writeln!(out, "URL: {}", homepage).unwrap();
+    }
+
+    if !options.requires.is_empty() {
+        writeln!(out, "Requires: {}", options.requires.join(", ")).unwrap();
+    }
+
+    if !options.requires_private.is_empty() {
+        writeln!(out, "Requires.private: {}", options.requires_private.join(", ")).unwrap();
+    }
+
+    writeln!(out, "Cflags: -I${{includedir}}").unwrap();
+    writeln!(out, "Libs: -L${{libdir}} -l{}", name).unwrap();
+
+    let private_libs: Vec<String> = project
+        .get_library_dirs()
+        .iter()
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(|dir| format!("-L{}", dir))
+        .chain(project.get_libraries().iter().map(|lib| format!("-l{}", lib)))
+        .collect();
+
+    if !private_libs.is_empty() {
+        writeln!(out, "Libs.private: {}", private_libs.join(" ")).unwrap();
+    }
+
+    out
+}
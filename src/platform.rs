@@ -60,21 +60,10 @@ impl Default for PlatformOptions {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Default)]
 pub struct Platforms {
     pub linux: Option<PlatformOptions>,
     pub bsd: Option<PlatformOptions>,
     pub osx: Option<PlatformOptions>,
     pub windows: Option<PlatformOptions>,
 }
-
-impl Default for Platforms {
-    fn default() -> Self {
-        Self {
-            linux: None,
-            bsd: None,
-            osx: None,
-            windows: None,
-        }
-    }
-}
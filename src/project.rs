@@ -1,6 +1,10 @@
-use crate::{compiler::*, language::*, platform::*};
+use crate::{common::*, compiler::*, language::*, metadata::*, platform::*};
 use serde_derive::*;
-use std::{collections::hash_map::Values, collections::HashMap, env, path::PathBuf};
+use std::{
+    collections::{hash_map::Values, BTreeMap, BTreeSet, HashMap},
+    env, fmt,
+    path::{Component, Path, PathBuf},
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct DirectoryHashMap(HashMap<String, String>);
@@ -15,16 +19,14 @@ pub struct Binary {
 
 impl DirectoryHashMap {
     pub fn new() -> Self {
-        Self {
-            0: {
-                let mut hm = HashMap::new();
-                hm.insert("build_dir".to_string(), "./build".to_string());
-                hm.insert("object_dir".to_string(), "./obj".to_string());
-                hm.insert("source_dir".to_string(), "./src".to_string());
+        Self({
+            let mut hm = HashMap::new();
+            hm.insert("build_dir".to_string(), "./build".to_string());
+            hm.insert("object_dir".to_string(), "./obj".to_string());
+            hm.insert("source_dir".to_string(), "./src".to_string());
 
-                hm
-            },
-        }
+            hm
+        })
     }
 
     pub fn get_all_dirs(&self) -> Values<'_, String, String> { self.0.values() }
@@ -48,21 +50,250 @@ impl DirectoryHashMap {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LintOptions {
+    #[serde(default = "LintOptions::default_command")]
+    pub command: String,
+    #[serde(default)]
+    pub checks: Vec<String>,
+    #[serde(default)]
+    pub deny_warnings: bool,
+}
+
+impl LintOptions {
+    fn default_command() -> String { "clang-tidy".to_string() }
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            command: Self::default_command(),
+            checks: Vec::default(),
+            deny_warnings: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FormatOptions {
+    #[serde(default = "FormatOptions::default_command")]
+    pub command: String,
+    #[serde(default = "FormatOptions::default_style")]
+    pub style: String,
+}
+
+impl FormatOptions {
+    fn default_command() -> String { "clang-format".to_string() }
+    fn default_style() -> String { "LLVM".to_string() }
+
+    pub fn get_clang_format_config(&self) -> String { format!("---\nBasedOnStyle: {}\n...\n", self.style) }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            command: Self::default_command(),
+            style: Self::default_style(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct Hooks {
+    #[serde(default)]
+    pub pre_build: Vec<String>,
+    #[serde(default)]
+    pub post_build: Vec<String>,
+    #[serde(default)]
+    pub pre_run: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct AssetOptions {
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub symlink: bool,
+    #[serde(default)]
+    pub run_in_output_dir: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct PkgConfigOptions {
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub requires_private: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct Feature {
+    #[serde(default)]
+    pub defines: Vec<String>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct Features {
+    #[serde(default)]
+    pub default: Vec<String>,
+    #[serde(flatten)]
+    pub features: BTreeMap<String, Feature>,
+}
+
+impl Features {
+    // Returns the requested features along with the ones that they enable,
+    // recursively.
+    fn resolve(
+        &self,
+        requested: &[String],
+        default_features: bool,
+        all_features: bool,
+    ) -> Result<BTreeSet<String>, StrRet> {
+        let mut pending: Vec<String> = requested.to_vec();
+
+        if default_features {
+            pending.extend(self.default.iter().cloned());
+        }
+
+        if all_features {
+            pending.extend(self.features.keys().cloned());
+        }
+
+        let mut enabled = BTreeSet::new();
+
+        while let Some(name) = pending.pop() {
+            let feature = match self.features.get(&name) {
+                Some(feature) => feature,
+                None => return Err(format!("The feature \"{}\" is not defined in [features]", name).into()),
+            };
+
+            if enabled.insert(name) {
+                pending.extend(feature.features.iter().cloned());
+            }
+        }
+
+        Ok(enabled)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct ChecksOptions {
+    #[serde(default)]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub functions: Vec<String>,
+    #[serde(default)]
+    pub type_sizes: Vec<String>,
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    // Maps each symbol to the header that should declare it. Being a table, it
+    // has to come after the other keys.
+    #[serde(default)]
+    pub symbols: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct CacheOptions {
+    // Whether objects are also stored in and restored from the cache that is
+    // shared between projects.
+    #[serde(default)]
+    pub shared: bool,
+    // An http:// URL of a server, such as one run by `ocean cache serve`, that
+    // objects are fetched from and uploaded to.
+    pub remote: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct ConfigHeaderOptions {
+    #[serde(default)]
+    pub git_commit: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DebOptions {
+    pub maintainer: Option<String>,
+    #[serde(default)]
+    pub depends: Vec<String>,
+    #[serde(default = "DebOptions::default_section")]
+    pub section: String,
+    #[serde(default = "DebOptions::default_priority")]
+    pub priority: String,
+}
+
+impl DebOptions {
+    fn default_section() -> String { "misc".to_string() }
+    fn default_priority() -> String { "optional".to_string() }
+}
+
+impl Default for DebOptions {
+    fn default() -> Self {
+        Self {
+            maintainer: None,
+            depends: Vec::default(),
+            section: Self::default_section(),
+            priority: Self::default_priority(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Eq, PartialEq, Copy, Clone, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectKind {
+    #[default]
+    Executable,
+    Static,
+    Shared,
+}
+
+impl fmt::Display for ProjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ProjectKind::Executable => "executable",
+            ProjectKind::Static => "static",
+            ProjectKind::Shared => "shared",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 struct Inner {
     name: String,
+    version: Option<String>,
+    authors: Option<Vec<String>>,
+    description: Option<String>,
+    license: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
     language: Language,
+    kind: Option<ProjectKind>,
     libraries: Vec<String>,
     library_directories: Vec<String>,
+    include_directories: Option<Vec<String>>,
 }
 
 impl Default for Inner {
     fn default() -> Self {
         Self {
             name: "Ocean Project".to_string(),
+            version: None,
+            authors: None,
+            description: None,
+            license: None,
+            homepage: None,
+            repository: None,
             language: Language::C,
+            kind: None,
             libraries: Vec::default(),
             library_directories: Vec::default(),
+            include_directories: None,
         }
     }
 }
@@ -74,6 +305,20 @@ pub struct Project {
     compiler: Compiler,
     platforms: Option<Platforms>,
     bins: Option<Vec<Binary>>,
+    lint: Option<LintOptions>,
+    format: Option<FormatOptions>,
+    hooks: Option<Hooks>,
+    resources: Option<BTreeMap<String, String>>,
+    assets: Option<AssetOptions>,
+    pkg_config: Option<PkgConfigOptions>,
+    deb: Option<DebOptions>,
+    config_header: Option<ConfigHeaderOptions>,
+    checks: Option<ChecksOptions>,
+    cache: Option<CacheOptions>,
+    features: Option<Features>,
+    // The features chosen on the command line, or None for the default ones.
+    #[serde(skip)]
+    enabled_features: Option<BTreeSet<String>>,
 }
 
 impl Project {
@@ -114,6 +359,150 @@ impl Project {
     }
 
     pub fn get_name(&self) -> &String { &self.project.name }
+    pub fn get_version(&self) -> Option<&String> { self.project.version.as_ref() }
+    pub fn get_authors(&self) -> Vec<String> { self.project.authors.clone().unwrap_or_default() }
+    pub fn get_description(&self) -> Option<&String> { self.project.description.as_ref() }
+    pub fn get_license(&self) -> Option<&String> { self.project.license.as_ref() }
+    pub fn get_homepage(&self) -> Option<&String> { self.project.homepage.as_ref() }
+    pub fn get_repository(&self) -> Option<&String> { self.project.repository.as_ref() }
+
+    pub fn set_version(&mut self, version: String) -> Result<(), StrRet> {
+        validate_version(&version)?;
+        self.project.version = Some(version);
+
+        Ok(())
+    }
+
+    pub fn set_license(&mut self, license: String) -> Result<(), StrRet> {
+        validate_license(&license)?;
+        self.project.license = Some(license);
+
+        Ok(())
+    }
+
+    pub fn set_authors(&mut self, authors: Vec<String>) { self.project.authors = Some(authors); }
+    pub fn set_description(&mut self, description: String) { self.project.description = Some(description); }
+    pub fn set_homepage(&mut self, homepage: String) { self.project.homepage = Some(homepage); }
+    pub fn set_repository(&mut self, repository: String) { self.project.repository = Some(repository); }
+
+    // Checks the version and licence in the [project] section, since Ocean.toml
+    // may have been edited by hand. They do not affect the build, so problems
+    // are only warned about: failing would also stop `ocean set` from fixing
+    // them.
+    pub fn get_metadata_warnings(&self) -> Vec<StrRet> {
+        let mut warnings = vec![];
+
+        if let Some(Err(e)) = self.project.version.as_deref().map(validate_version) {
+            warnings.push(e);
+        }
+
+        if let Some(Err(e)) = self.project.license.as_deref().map(validate_license) {
+            warnings.push(e);
+        }
+
+        warnings
+    }
+
+    // Checks that the [features] section only refers to features that exist,
+    // since Ocean.toml may have been edited by hand.
+    pub fn validate(&self) -> Result<(), StrRet> {
+        let features = self.get_features();
+        let references = features
+            .default
+            .iter()
+            .chain(features.features.values().flat_map(|feature| feature.features.iter()));
+
+        for name in references {
+            if !features.features.contains_key(name) {
+                return Err(format!("The feature \"{}\" is not defined in [features]", name).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_features(&self) -> Features { self.features.clone().unwrap_or_default() }
+
+    // Enables the given features, plus the default ones unless
+    // `default_features` is false, or every feature if `all_features` is set.
+    pub fn select_features(
+        &mut self,
+        requested: &[String],
+        default_features: bool,
+        all_features: bool,
+    ) -> Result<(), StrRet> {
+        self.enabled_features = Some(self.get_features().resolve(requested, default_features, all_features)?);
+
+        Ok(())
+    }
+
+    pub fn get_enabled_features(&self) -> BTreeSet<String> {
+        match &self.enabled_features {
+            Some(enabled) => enabled.clone(),
+            None => self.get_features().resolve(&[], true, false).unwrap_or_default(),
+        }
+    }
+
+    fn get_enabled_feature_values(&self, values: impl Fn(&Feature) -> &Vec<String>) -> Vec<String> {
+        let features = self.get_features();
+        let mut result: Vec<String> = vec![];
+
+        for name in self.get_enabled_features() {
+            for value in features.features.get(&name).map(&values).into_iter().flatten() {
+                if !result.contains(value) {
+                    result.push(value.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn get_feature_defines(&self) -> Vec<String> { self.get_enabled_feature_values(|feature| &feature.defines) }
+    pub fn get_feature_sources(&self) -> Vec<String> { self.get_enabled_feature_values(|feature| &feature.sources) }
+
+    pub fn get_feature_libraries(&self) -> Vec<String> {
+        self.get_enabled_feature_values(|feature| &feature.libraries)
+    }
+
+    // Whether `path` is a source file of a feature that is not enabled, and so
+    // must not be compiled even if it is in the source directory.
+    pub fn is_disabled_feature_source(&self, path: &Path) -> bool {
+        let normalise = |path: &Path| -> PathBuf {
+            path.components()
+                .filter(|component| *component != Component::CurDir)
+                .collect()
+        };
+
+        let path = normalise(path);
+        let enabled = self.get_feature_sources();
+
+        self.get_features()
+            .features
+            .values()
+            .flat_map(|feature| feature.sources.iter())
+            .filter(|source| !enabled.contains(source))
+            .any(|source| normalise(Path::new(source)) == path)
+    }
+
+    pub fn get_kind(&self) -> ProjectKind { self.project.kind.unwrap_or_default() }
+    pub fn set_kind(&mut self, kind: ProjectKind) { self.project.kind = Some(kind); }
+    pub fn get_include_dirs(&self) -> Vec<String> { self.project.include_directories.clone().unwrap_or_default() }
+
+    // The file name of the project's main build output.
+    pub fn get_output_name(&self) -> String {
+        match self.get_kind() {
+            ProjectKind::Executable => format!("{}{}", self.project.name, env::consts::EXE_SUFFIX),
+            ProjectKind::Static => format!("lib{}.a", self.project.name),
+            ProjectKind::Shared => format!(
+                "{}{}{}",
+                env::consts::DLL_PREFIX,
+                self.project.name,
+                env::consts::DLL_SUFFIX
+            ),
+        }
+    }
+
     pub fn get_platform(&self) -> &Option<Platforms> { &self.platforms }
     pub fn get_platform_mut(&mut self) -> &mut Option<Platforms> { &mut self.platforms }
     pub fn set_language(&mut self, lang: Language) { self.project.language = lang; }
@@ -150,6 +539,18 @@ impl Project {
         self.get_compiler_mut().set_compiler_command(language, compiler_command);
     }
 
+    pub fn get_lint_options(&self) -> LintOptions { self.lint.clone().unwrap_or_default() }
+    pub fn get_format_options(&self) -> FormatOptions { self.format.clone().unwrap_or_default() }
+    pub fn set_format_options(&mut self, options: FormatOptions) { self.format = Some(options); }
+    pub fn get_hooks(&self) -> Hooks { self.hooks.clone().unwrap_or_default() }
+    pub fn get_pkg_config_options(&self) -> PkgConfigOptions { self.pkg_config.clone().unwrap_or_default() }
+    pub fn get_checks_options(&self) -> Option<ChecksOptions> { self.checks.clone() }
+    pub fn get_config_header_options(&self) -> Option<ConfigHeaderOptions> { self.config_header.clone() }
+    pub fn get_cache_options(&self) -> CacheOptions { self.cache.clone().unwrap_or_default() }
+    pub fn get_deb_options(&self) -> DebOptions { self.deb.clone().unwrap_or_default() }
+    pub fn get_asset_options(&self) -> AssetOptions { self.assets.clone().unwrap_or_default() }
+    pub fn get_resources(&self) -> BTreeMap<String, String> { self.resources.clone().unwrap_or_default() }
+
     pub fn get_binaries(&self) -> Vec<Binary> {
         if let Some(bins) = self.bins.clone() {
             return bins;
@@ -167,6 +568,127 @@ impl Default for Project {
             compiler: Compiler::default(),
             platforms: None,
             bins: None,
+            lint: None,
+            format: None,
+            hooks: None,
+            resources: None,
+            assets: None,
+            pkg_config: None,
+            deb: None,
+            config_header: None,
+            checks: None,
+            cache: None,
+            features: None,
+            enabled_features: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_project(features: &str) -> Project {
+        toml::from_str(&format!(
+            "[project]
+name = 'demo'
+language = 'C'
+libraries = []
+library_directories = []
+
+[directories]
+build_dir = './build'
+source_dir = './src'
+object_dir = './obj'
+
+[compiler.c]
+command = 'gcc'
+flags = []
+
+[compiler.cxx]
+command = 'g++'
+flags = []
+
+[features]
+{}",
+            features
+        ))
+        .unwrap()
+    }
+
+    fn get_default_project() -> Project {
+        get_project(
+            "default = ['json']
+json = { defines = ['HAVE_JSON'], sources = ['src/json.c'], features = ['strings'] }
+strings = { defines = ['HAVE_STRINGS', 'HAVE_JSON'], sources = ['./extra/strings.c'] }
+tls = { defines = ['HAVE_TLS'], sources = ['src/tls.c'], libraries = ['ssl'], features = ['net'] }
+net = { defines = ['HAVE_NET'], features = ['tls'] }",
+        )
+    }
+
+    fn names(names: &[&str]) -> BTreeSet<String> { names.iter().map(|name| name.to_string()).collect() }
+
+    #[test]
+    fn default_features_enable_their_requirements() {
+        let project = get_default_project();
+
+        assert!(project.validate().is_ok());
+        assert_eq!(project.get_enabled_features(), names(&["json", "strings"]));
+        assert_eq!(project.get_feature_defines(), ["HAVE_JSON", "HAVE_STRINGS"]);
+        assert_eq!(project.get_feature_sources(), ["src/json.c", "./extra/strings.c"]);
+        assert!(project.get_feature_libraries().is_empty());
+    }
+
+    #[test]
+    fn features_are_selected_from_the_command_line() {
+        let mut project = get_default_project();
+
+        // Features that enable each other are only resolved once.
+        project.select_features(&["net".to_string()], false, false).unwrap();
+        assert_eq!(project.get_enabled_features(), names(&["net", "tls"]));
+        assert_eq!(project.get_feature_defines(), ["HAVE_NET", "HAVE_TLS"]);
+        assert_eq!(project.get_feature_libraries(), ["ssl"]);
+
+        project.select_features(&["tls".to_string()], true, false).unwrap();
+        assert_eq!(project.get_enabled_features(), names(&["json", "net", "strings", "tls"]));
+
+        project.select_features(&[], false, true).unwrap();
+        assert_eq!(project.get_enabled_features(), names(&["json", "net", "strings", "tls"]));
+
+        project.select_features(&[], false, false).unwrap();
+        assert!(project.get_enabled_features().is_empty());
+    }
+
+    #[test]
+    fn unknown_features_are_errors() {
+        let mut project = get_default_project();
+        assert!(project.select_features(&["xml".to_string()], true, false).is_err());
+
+        assert!(get_project("default = ['xml']").validate().is_err());
+        assert!(get_project("json = { features = ['xml'] }").validate().is_err());
+
+        // Without any features, nothing is enabled.
+        let mut project = get_project("");
+        assert!(project.get_enabled_features().is_empty());
+
+        project.features = None;
+        assert!(project.validate().is_ok());
+        assert!(project.get_enabled_features().is_empty());
+    }
+
+    #[test]
+    fn sources_of_disabled_features_are_excluded() {
+        let mut project = get_default_project();
+
+        assert!(project.is_disabled_feature_source(Path::new("src/tls.c")));
+        assert!(project.is_disabled_feature_source(Path::new("./src/tls.c")));
+        assert!(!project.is_disabled_feature_source(Path::new("src/json.c")));
+        assert!(!project.is_disabled_feature_source(Path::new("extra/strings.c")));
+        assert!(!project.is_disabled_feature_source(Path::new("src/main.c")));
+
+        project.select_features(&["tls".to_string()], false, false).unwrap();
+        assert!(!project.is_disabled_feature_source(Path::new("src/tls.c")));
+        assert!(project.is_disabled_feature_source(Path::new("src/json.c")));
+        assert!(project.is_disabled_feature_source(Path::new("extra/strings.c")));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{common::*, shared_cache::SharedCache};
+use std::{
+    io::{prelude::*, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+// Objects larger than this are refused by `ocean cache serve`.
+const MAX_OBJECT_SIZE: usize = 512 << 20;
+
+// Header names, in lowercase, and values.
+type Headers = Vec<(String, String)>;
+
+fn is_key(key: &str) -> bool { key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) }
+
+// A cache of objects on another machine, reached with plain HTTP: objects are
+// fetched with GET <url>/<key> and uploaded with PUT <url>/<key>. Any error is
+// treated as a miss, and a server that cannot be reached is not tried again
+// during the same build, so builds carry on with the local cache alone.
+pub struct RemoteCache {
+    host: String,
+    prefix: String,
+    reachable: AtomicBool,
+}
+
+impl RemoteCache {
+    pub fn new(url: &str) -> Result<Self, StrRet> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Err(format!("Invalid remote cache URL \"{}\", only http:// URLs are supported", url).into()),
+        };
+
+        let (host, prefix) = match rest.find('/') {
+            Some(index) => (&rest[..index], rest[index..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+
+        if host.is_empty() {
+            return Err(format!("Invalid remote cache URL \"{}\", it has no host", url).into());
+        }
+
+        Ok(Self {
+            host: if host.contains(':') { host.to_string() } else { format!("{}:80", host) },
+            prefix: prefix.to_string(),
+            reachable: AtomicBool::new(true),
+        })
+    }
+
+    fn connect(&self) -> Option<TcpStream> {
+        if !self.reachable.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let stream = self
+            .host
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .and_then(|address| TcpStream::connect_timeout(&address, Duration::from_secs(2)).ok());
+
+        match stream {
+            Some(stream) => {
+                stream.set_read_timeout(Some(Duration::from_secs(30))).ok()?;
+                stream.set_write_timeout(Some(Duration::from_secs(30))).ok()?;
+                Some(stream)
+            },
+            None => {
+                self.reachable.store(false, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+
+    // Sends a request and returns the status code and body of the response.
+    // HTTP/1.0 is used so that servers send the body as is, and close the
+    // connection after it.
+    fn request(&self, method: &str, key: &str, body: &[u8]) -> Option<(u16, Vec<u8>)> {
+        let mut stream = self.connect()?;
+
+        write!(
+            stream,
+            "{} {}/{} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n",
+            method,
+            self.prefix,
+            key,
+            self.host,
+            body.len()
+        )
+        .ok()?;
+        stream.write_all(body).ok()?;
+
+        let mut reader = BufReader::new(stream);
+        let (start, headers) = read_head(&mut reader)?;
+        let mut body = vec![];
+        reader.read_to_end(&mut body).ok()?;
+
+        // A connection that was cut off early must not leave a truncated object.
+        match get_content_length(&headers) {
+            Some(length) if length != body.len() => None,
+            _ => Some((start.get(1)?.parse().ok()?, body)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        match self.request("GET", key, &[])? {
+            (200, body) => Some(body),
+            _ => None,
+        }
+    }
+
+    pub fn put(&self, key: &str, object: &[u8]) { self.request("PUT", key, object); }
+}
+
+// Reads the start line of a request or response, split into words, and its
+// headers, with their names in lowercase.
+fn read_head(reader: &mut impl BufRead) -> Option<(Vec<String>, Headers)> {
+    let mut start = String::new();
+    reader.read_line(&mut start).ok()?;
+
+    let mut headers = vec![];
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+
+    Some((start.split_whitespace().map(String::from).collect(), headers))
+}
+
+fn get_content_length(headers: &Headers) -> Option<usize> {
+    headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &[u8]) {
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )
+    .and_then(|_| stream.write_all(body))
+    .unwrap_or(());
+}
+
+fn handle(mut stream: TcpStream, cache: &SharedCache) {
+    stream.set_read_timeout(Some(Duration::from_secs(30))).unwrap_or(());
+
+    let mut reader = match stream.try_clone() {
+        Ok(stream) => BufReader::new(stream),
+        Err(_) => return,
+    };
+
+    let (start, headers) = match read_head(&mut reader) {
+        Some((start, headers)) if start.len() >= 2 => (start, headers),
+        _ => return,
+    };
+    let (method, path) = (&start[0], &start[1]);
+    let key = path.rsplit('/').next().unwrap_or_default();
+
+    let status = if !is_key(key) {
+        "404 Not Found"
+    } else {
+        match method.as_str() {
+            "GET" => match cache.read(key) {
+                Some(object) => {
+                    respond(&mut stream, "200 OK", &object);
+                    println!("{} {} 200", method, path);
+                    return;
+                },
+                None => "404 Not Found",
+            },
+            "PUT" => match get_content_length(&headers) {
+                Some(length) if length <= MAX_OBJECT_SIZE => {
+                    let mut object = vec![0; length];
+
+                    if reader.read_exact(&mut object).is_err() {
+                        return;
+                    }
+
+                    cache.write(key, &object);
+                    "201 Created"
+                },
+                Some(_) => "413 Payload Too Large",
+                None => "411 Length Required",
+            },
+            _ => "405 Method Not Allowed",
+        }
+    };
+
+    respond(&mut stream, status, &[]);
+    println!("{} {} {}", method, path, &status[..3]);
+}
+
+// Serves the objects in `cache` to other machines until the process is
+// stopped, handling each connection on its own thread.
+pub fn serve(listener: TcpListener, cache: SharedCache) {
+    let cache = Arc::new(cache);
+
+    for stream in listener.incoming().flatten() {
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || handle(stream, &cache));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs::remove_dir_all, process};
+
+    #[test]
+    fn objects_round_trip_through_the_server() {
+        let directory = env::temp_dir().join(format!("ocean-remote-cache-test-{}", process::id()));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_cache = SharedCache::at(directory.clone());
+        thread::spawn(move || serve(listener, server_cache));
+
+        let remote = RemoteCache::new(&format!("http://{}/objects", address)).unwrap();
+        let key = "ab".repeat(32);
+
+        assert_eq!(remote.get(&key), None);
+
+        remote.put(&key, b"object contents");
+        assert_eq!(remote.get(&key).as_deref(), Some(&b"object contents"[..]));
+        assert_eq!(remote.get("../../etc/passwd"), None);
+
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn unreachable_servers_are_misses() {
+        // Binding and dropping a listener leaves a port that nothing listens on.
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let remote = RemoteCache::new(&format!("http://{}", address)).unwrap();
+
+        assert_eq!(remote.get(&"cd".repeat(32)), None);
+        assert!(!remote.reachable.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn only_http_urls_are_accepted() {
+        assert!(RemoteCache::new("https://cache.example.com").is_err());
+        assert!(RemoteCache::new("http://").is_err());
+        assert_eq!(RemoteCache::new("http://cache.example.com").unwrap().host, "cache.example.com:80");
+        assert_eq!(RemoteCache::new("http://localhost:8080/ocean/").unwrap().prefix, "/ocean");
+    }
+}
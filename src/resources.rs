@@ -0,0 +1,357 @@
+use crate::{cache::Cache, common::*, project::Project};
+use std::{fmt::Write, fs::read, path::PathBuf};
+
+pub const RESOURCES_HEADER: &str = "ocean_resources.h";
+const RESOURCES_SOURCE: &str = "ocean_resources.c";
+
+pub struct Resource {
+    pub symbol: String,
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct Resources {
+    pub resources: Vec<Resource>,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn sanitise(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+impl Resources {
+    pub fn get_directory(project: &Project, build_mode: &str) -> String {
+        format!(
+            "{}/{}/resources",
+            project.get_directories().get_objects_dir(),
+            build_mode
+        )
+    }
+
+    pub fn get_source_path(project: &Project, build_mode: &str) -> String {
+        format!("{}/{}", Self::get_directory(project, build_mode), RESOURCES_SOURCE)
+    }
+
+    pub fn get_header_path(project: &Project, build_mode: &str) -> String {
+        format!("{}/{}", Self::get_directory(project, build_mode), RESOURCES_HEADER)
+    }
+
+    pub fn get_object_path(project: &Project, build_mode: &str) -> String {
+        format!("{}/resources.o", Self::get_directory(project, build_mode))
+    }
+
+    pub fn get_hash_path(project: &Project, build_mode: &str) -> String {
+        format!("{}/resources.hash", Self::get_directory(project, build_mode))
+    }
+
+    // Reads every file named in the [resources] section. A plain path is
+    // embedded under its own symbol name, while each file matched by a glob is
+    // embedded as `<symbol>_<file name>`.
+    pub fn collect(project: &Project) -> Result<Self, StrRet> {
+        let mut resources: Vec<Resource> = vec![];
+
+        for (symbol, pattern) in project.get_resources() {
+            if !is_identifier(&symbol) {
+                return Err(format!("Resource name \"{}\" is not a valid C identifier", symbol).into());
+            }
+
+            let mut files = vec![];
+
+            if pattern.contains(['*', '?', '[']) {
+                let paths = match glob::glob(&pattern) {
+                    Ok(paths) => paths,
+                    Err(e) => return Err(format!("Invalid resource pattern \"{}\": {}", pattern, e).into()),
+                };
+
+                for path in paths.flatten().filter(|path| path.is_file()) {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    files.push((format!("{}_{}", symbol, sanitise(&name)), path));
+                }
+
+                if files.is_empty() {
+                    return Err(format!("Resource pattern \"{}\" did not match any files", pattern).into());
+                }
+            } else {
+                files.push((symbol, PathBuf::from(pattern)));
+            }
+
+            for (symbol, path) in files {
+                if resources.iter().any(|resource| resource.symbol == symbol) {
+                    return Err(format!("Resource symbol \"{}\" is defined more than once", symbol).into());
+                }
+
+                let contents = match read(&path) {
+                    Ok(contents) => contents,
+                    Err(e) => return Err(format!("Could not read resource \"{}\": {}", path.display(), e).into()),
+                };
+
+                resources.push(Resource { symbol, path, contents });
+            }
+        }
+
+        Ok(Self { resources })
+    }
+
+    pub fn is_empty(&self) -> bool { self.resources.is_empty() }
+
+    pub fn get_hash(&self) -> String {
+        let mut contents = vec![];
+
+        for resource in &self.resources {
+            contents.extend_from_slice(resource.symbol.as_bytes());
+            contents.push(0);
+            contents.extend_from_slice(resource.path.to_string_lossy().as_bytes());
+            contents.push(0);
+            contents.extend_from_slice(&resource.contents);
+        }
+
+        Cache::hash_bytes(&contents)
+    }
+
+    pub fn to_header(&self) -> String {
+        let mut out = String::from(
+            "/* Generated by Ocean from the [resources] section of Ocean.toml. Do not edit. */\n#ifndef \
+             OCEAN_RESOURCES_H\n#define OCEAN_RESOURCES_H\n\n#include <stddef.h>\n\n#ifdef __cplusplus\nextern \"C\" \
+             {\n#endif\n\n",
+        );
+
+        for resource in &self.resources {
+            writeln!(out, "extern const unsigned char {}[];", resource.symbol).unwrap();
+            writeln!(out, "extern const size_t {}_size;", resource.symbol).unwrap();
+        }
+
+        out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+        out
+    }
+
+    // Every array gets a trailing NUL that is not counted in its size, so text
+    // resources can be used as C strings and empty files still produce a valid
+    // initialiser.
+    pub fn to_source(&self) -> String {
+        let mut out = format!(
+            "/* Generated by Ocean from the [resources] section of Ocean.toml. Do not edit. */\n#include \"{}\"\n",
+            RESOURCES_HEADER
+        );
+
+        for resource in &self.resources {
+            // A path can contain "*/", which would end the comment early.
+            let path = resource.path.display().to_string().replace("*/", "*\\/");
+            writeln!(out, "\n/* {} */", path).unwrap();
+            writeln!(out, "const unsigned char {}[] = {{", resource.symbol).unwrap();
+
+            for chunk in resource.contents.chunks(16) {
+                let bytes: Vec<String> = chunk.iter().map(|byte| format!("0x{:02x},", byte)).collect();
+                writeln!(out, "    {}", bytes.join(" ")).unwrap();
+            }
+
+            writeln!(out, "    0x00\n}};").unwrap();
+            writeln!(
+                out,
+                "const size_t {}_size = {};",
+                resource.symbol,
+                resource.contents.len()
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        fs::{create_dir_all, remove_dir_all, write},
+        process,
+    };
+
+    fn get_project(resources: &str) -> Project {
+        toml::from_str(&format!(
+            "[project]
+name = 'demo'
+language = 'C'
+libraries = []
+library_directories = []
+
+[directories]
+build_dir = './build'
+source_dir = './src'
+object_dir = './obj'
+
+[compiler.c]
+command = 'gcc'
+flags = []
+
+[compiler.cxx]
+command = 'g++'
+flags = []
+
+[resources]
+{}",
+            resources
+        ))
+        .unwrap()
+    }
+
+    fn resource(symbol: &str, path: &str, contents: &[u8]) -> Resource {
+        Resource {
+            symbol: symbol.to_string(),
+            path: PathBuf::from(path),
+            contents: contents.to_vec(),
+        }
+    }
+
+    #[test]
+    fn source_defines_arrays_and_sizes() {
+        let resources = Resources {
+            resources: vec![
+                resource("greeting", "assets/hello.txt", b"Hello, world!\n\x00\xff"),
+                resource("empty", "assets/*/empty", b""),
+            ],
+        };
+
+        assert_eq!(
+            resources.to_source(),
+            "/* Generated by Ocean from the [resources] section of Ocean.toml. Do not edit. */
+#include \"ocean_resources.h\"
+
+/* assets/hello.txt */
+const unsigned char greeting[] = {
+    0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x77, 0x6f, 0x72, 0x6c, 0x64, 0x21, 0x0a, 0x00, 0xff,
+    0x00
+};
+const size_t greeting_size = 16;
+
+/* assets/*\\/empty */
+const unsigned char empty[] = {
+    0x00
+};
+const size_t empty_size = 0;
+"
+        );
+    }
+
+    #[test]
+    fn header_declares_arrays_and_sizes() {
+        let resources = Resources {
+            resources: vec![resource("logo", "logo.png", b"png"), resource("icons_1x_png", "icons/1x.png", b"")],
+        };
+
+        assert_eq!(
+            resources.to_header(),
+            "/* Generated by Ocean from the [resources] section of Ocean.toml. Do not edit. */
+#ifndef OCEAN_RESOURCES_H
+#define OCEAN_RESOURCES_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+
+extern const unsigned char logo[];
+extern const size_t logo_size;
+extern const unsigned char icons_1x_png[];
+extern const size_t icons_1x_png_size;
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
+"
+        );
+    }
+
+    #[test]
+    fn generated_files_compile() {
+        let directory = env::temp_dir().join(format!("ocean-resources-compile-test-{}", process::id()));
+        create_dir_all(&directory).unwrap();
+
+        let resources = Resources {
+            resources: vec![resource("text", "text.txt", b"abc"), resource("icons_1x_png", "*/1x.png", b"")],
+        };
+        write(directory.join(RESOURCES_HEADER), resources.to_header()).unwrap();
+        write(directory.join(RESOURCES_SOURCE), resources.to_source()).unwrap();
+        write(
+            directory.join("check.c"),
+            "#include \"ocean_resources.c\"\n\
+             _Static_assert(sizeof(text) == 4, \"arrays end with a NUL\");\n",
+        )
+        .unwrap();
+
+        // Skipped where there is no C compiler.
+        let status = process::Command::new("cc")
+            .args(["-fsyntax-only", "-std=c11", "-Wall", "-Werror"])
+            .arg(directory.join("check.c"))
+            .status();
+
+        if let Ok(status) = status {
+            assert!(status.success());
+        }
+
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn names_are_valid_identifiers() {
+        assert!(is_identifier("logo"));
+        assert!(is_identifier("_logo2"));
+        assert!(!is_identifier("2logo"));
+        assert!(!is_identifier("app-logo"));
+        assert!(!is_identifier("logo.png"));
+        assert!(!is_identifier(""));
+
+        assert_eq!(sanitise("app-icon.png"), "app_icon_png");
+        assert_eq!(sanitise("1x.data"), "1x_data");
+        assert_eq!(sanitise("read me.txt"), "read_me_txt");
+    }
+
+    #[test]
+    fn globs_embed_each_file_under_its_own_symbol() {
+        let directory = env::temp_dir().join(format!("ocean-resources-test-{}", process::id()));
+        let icons = directory.join("icons");
+        create_dir_all(&icons).unwrap();
+
+        write(icons.join("app-icon.png"), "a").unwrap();
+        write(icons.join("1x.data"), "bc").unwrap();
+        write(directory.join("logo.svg"), "<svg/>").unwrap();
+
+        let project = get_project(&format!(
+            "icons = '{}/*'\nlogo = '{}'",
+            icons.display(),
+            directory.join("logo.svg").display()
+        ));
+        let resources = Resources::collect(&project).unwrap();
+        let symbols: Vec<(&str, usize)> = resources
+            .resources
+            .iter()
+            .map(|resource| (resource.symbol.as_str(), resource.contents.len()))
+            .collect();
+
+        assert_eq!(symbols, [("icons_1x_data", 2), ("icons_app_icon_png", 1), ("logo", 6)]);
+
+        // Every symbol has to be a C identifier, defined once.
+        let invalid = get_project(&format!("2icons = '{}/*'", icons.display()));
+        assert!(Resources::collect(&invalid).is_err());
+
+        write(icons.join("app.icon.png"), "d").unwrap();
+        assert!(Resources::collect(&project).is_err());
+
+        let unmatched = get_project(&format!("icons = '{}/*.missing'", icons.display()));
+        assert!(Resources::collect(&unmatched).is_err());
+
+        remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{cache::Cache, common::*, remote_cache::RemoteCache};
+use serde_derive::*;
+use std::{
+    env::{self, current_dir},
+    fs::{create_dir_all, metadata, read, read_to_string, remove_file, rename, write, File},
+    io::prelude::*,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+struct Stats {
+    hits: u64,
+    misses: u64,
+}
+
+// A cache of object files that is shared between every project of the user,
+// in $OCEAN_CACHE_DIR or ~/.cache/ocean. Objects are stored under a hash of
+// everything that goes into them, so a translation unit that was compiled
+// before, in any project, branch or build directory, is copied instead of
+// compiled again. A remote cache can be added behind it, in which case objects
+// that are not found locally are fetched from it, and new objects are uploaded
+// to it.
+pub struct SharedCache {
+    directory: PathBuf,
+    remote: Option<RemoteCache>,
+}
+
+impl SharedCache {
+    pub fn open() -> Self {
+        let directory = match env::var_os("OCEAN_CACHE_DIR").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+                Some(dir) => PathBuf::from(dir).join("ocean"),
+                None => env::var_os("HOME")
+                    .or_else(|| env::var_os("USERPROFILE"))
+                    .map(|home| PathBuf::from(home).join(".cache").join("ocean"))
+                    .unwrap_or_else(|| env::temp_dir().join("ocean-cache")),
+            },
+        };
+
+        Self::at(directory)
+    }
+
+    pub fn at(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            remote: None,
+        }
+    }
+
+    pub fn with_remote(self, remote: RemoteCache) -> Self {
+        Self {
+            remote: Some(remote),
+            ..self
+        }
+    }
+
+    fn get_objects_dir(&self) -> PathBuf { self.directory.join("objects") }
+
+    fn get_stats_path(&self) -> PathBuf { self.directory.join("stats.toml") }
+
+    fn get_object_path(&self, key: &str) -> PathBuf {
+        self.get_objects_dir().join(&key[..2]).join(format!("{}.o", key))
+    }
+
+    // Hashes the preprocessed source of the translation unit that `command`
+    // compiles together with `fingerprint`, which covers the command line, the
+    // compiler and the environment. Objects with debug information record the
+    // directory they were compiled in, so the directory is hashed too for them.
+    // Returns None if the source could not be preprocessed.
+    pub fn get_key(command: &Command, fingerprint: &str) -> Option<String> {
+        let output = Command::new(command.get_program())
+            .args(command.get_args())
+            .arg("-E")
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let mut contents = output.stdout;
+        contents.extend_from_slice(fingerprint.as_bytes());
+
+        if command.get_args().any(|arg| arg.to_string_lossy().starts_with("-g")) {
+            contents.extend_from_slice(current_dir().unwrap_or_default().to_string_lossy().as_bytes());
+        }
+
+        Some(Cache::hash_bytes(&contents))
+    }
+
+    // Returns the object stored locally under `key`, marking it as used so that
+    // garbage collection removes the least recently used objects first.
+    pub fn read(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.get_object_path(key);
+        let object = read(&path).ok()?;
+
+        if let Ok(file) = File::options().write(true).open(&path) {
+            file.set_modified(SystemTime::now()).unwrap_or(());
+        }
+
+        Some(object)
+    }
+
+    // Stores `object` locally under `key`. Objects are written to a temporary
+    // file first, so that concurrent builds never see half of one. The cache
+    // is only an optimisation, so failing to store an object is not an error.
+    pub fn write(&self, key: &str, object: &[u8]) {
+        let path = self.get_object_path(key);
+        let temporary = path.with_extension(format!("{}.tmp", process::id()));
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).unwrap_or(());
+        }
+
+        if write(&temporary, object).and_then(|_| rename(&temporary, &path)).is_err() {
+            remove_file(&temporary).unwrap_or(());
+        }
+    }
+
+    // Copies the object stored under `key` to `destination`, fetching it from
+    // the remote cache if there is one and it is not stored locally. Like a
+    // compile, it goes through a temporary file. Returns whether the object
+    // was restored.
+    pub fn restore(&self, key: &str, destination: &Path) -> bool {
+        let object = match self.read(key) {
+            Some(object) => object,
+            None => match self.remote.as_ref().and_then(|remote| remote.get(key)) {
+                Some(object) => {
+                    self.write(key, &object);
+                    object
+                },
+                None => return false,
+            },
+        };
+
+        let temporary = destination.with_extension("o.tmp");
+
+        if write(&temporary, object).and_then(|_| rename(&temporary, destination)).is_err() {
+            remove_file(&temporary).unwrap_or(());
+            return false;
+        }
+
+        true
+    }
+
+    // Stores a copy of `object` under `key`, and uploads it to the remote
+    // cache if there is one.
+    pub fn store(&self, key: &str, object: &Path) {
+        if let Ok(object) = read(object) {
+            self.write(key, &object);
+
+            if let Some(remote) = &self.remote {
+                remote.put(key, &object);
+            }
+        }
+    }
+
+    fn load_stats(&self) -> Stats {
+        read_to_string(self.get_stats_path())
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    // Adds the hits and misses of a build to the totals shown by `ocean cache
+    // stats`.
+    pub fn record(&self, hits: u64, misses: u64) {
+        let mut stats = self.load_stats();
+        stats.hits += hits;
+        stats.misses += misses;
+
+        if let Ok(contents) = toml::to_string(&stats) {
+            create_dir_all(&self.directory).unwrap_or(());
+            File::create(self.get_stats_path())
+                .and_then(|mut file| file.write_all(contents.as_bytes()))
+                .unwrap_or(());
+        }
+    }
+
+    // Returns every stored object with its size and when it was last used.
+    fn get_entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        get_files_recursive(&self.get_objects_dir())
+            .into_iter()
+            .filter(|path| path.extension().and_then(|extension| extension.to_str()) == Some("o"))
+            .filter_map(|path| {
+                let metadata = metadata(&path).ok()?;
+                let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+
+                Some((path, metadata.len(), modified))
+            })
+            .collect()
+    }
+
+    pub fn print_stats(&self) {
+        let entries = self.get_entries();
+        let stats = self.load_stats();
+        let size: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        let lookups = stats.hits + stats.misses;
+
+        println!("Location:  {}", self.directory.display());
+        println!("Objects:   {}", entries.len());
+        println!("Size:      {}", format_size(size));
+        println!("Hits:      {}", stats.hits);
+        println!("Misses:    {}", stats.misses);
+
+        if lookups > 0 {
+            println!("Hit rate:  {:.1}%", stats.hits as f64 * 100.0 / lookups as f64);
+        }
+    }
+
+    // Removes the least recently used objects until the cache is no larger
+    // than `max_size` bytes. Returns how many objects were removed and how many
+    // bytes that freed.
+    pub fn gc(&self, max_size: u64) -> (usize, u64) {
+        let mut entries = self.get_entries();
+        let mut size: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        let (mut removed, mut freed) = (0, 0);
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, entry_size, _) in entries {
+            if size <= max_size {
+                break;
+            }
+
+            if remove_file(&path).is_ok() {
+                size -= entry_size;
+                freed += entry_size;
+                removed += 1;
+            }
+        }
+
+        (removed, freed)
+    }
+}
+
+// Parses a size such as 500M, 2G or 1048576. Suffixes are powers of 1024.
+pub fn parse_size(size: &str) -> Result<u64, StrRet> {
+    let invalid = || format!("Invalid size \"{}\", expected a number optionally followed by K, M, G or T", size);
+    let upper = size.trim().to_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match upper[digits.len()..].trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(invalid().into()),
+    };
+
+    match digits.trim().parse::<u64>() {
+        Ok(number) => Ok(number.saturating_mul(multiplier)),
+        Err(_) => Err(invalid().into()),
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::remove_dir_all, time::Duration};
+
+    #[test]
+    fn sizes_are_parsed_in_powers_of_1024() {
+        assert_eq!(parse_size("1048576").unwrap(), 1048576);
+        assert_eq!(parse_size("500M").unwrap(), 500 << 20);
+        assert_eq!(parse_size("2G").unwrap(), 2 << 30);
+        assert_eq!(parse_size("1KiB").unwrap(), 1024);
+        assert_eq!(parse_size(" 3 kb ").unwrap(), 3 << 10);
+        assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+        assert_eq!(parse_size("99999999999T").unwrap(), u64::MAX);
+
+        for invalid in ["", "G", "12X", "1.5G", "-1M", "ten"] {
+            assert!(parse_size(invalid).is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn sizes_are_formatted_with_the_largest_unit() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(500 << 20), "500.0 MiB");
+        assert_eq!(format_size(2 << 30), "2.0 GiB");
+        assert_eq!(format_size(2048 << 30), "2.0 TiB");
+        assert_eq!(format_size(2048 << 40), "2048.0 TiB");
+    }
+
+    #[test]
+    fn gc_removes_the_least_recently_used_objects() {
+        let directory = env::temp_dir().join(format!("ocean-shared-cache-test-{}", process::id()));
+        let cache = SharedCache::at(directory.clone());
+        let now = SystemTime::now();
+
+        for (key, age) in [("aa11", 300), ("bb22", 200), ("cc33", 100)] {
+            cache.write(key, &[0; 100]);
+
+            let file = File::options().write(true).open(cache.get_object_path(key)).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        // Reading an object counts as using it, so the oldest one is kept.
+        assert!(cache.read("aa11").is_some());
+
+        assert_eq!(cache.gc(300), (0, 0));
+        assert_eq!(cache.gc(200), (1, 100));
+        assert!(cache.read("bb22").is_none());
+        assert!(cache.read("aa11").is_some());
+        assert!(cache.read("cc33").is_some());
+
+        assert_eq!(cache.gc(0), (2, 200));
+        assert!(cache.get_entries().is_empty());
+
+        remove_dir_all(directory).unwrap();
+    }
+}